@@ -0,0 +1,32 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rust_static_site::attributes::Attributes;
+use rust_static_site::htmlnode::{HtmlNode, NodeKind};
+
+/// Mirror of `HtmlNode` that libfuzzer can build from raw bytes.
+#[derive(Arbitrary, Debug)]
+struct FuzzNode {
+    tag: Option<String>,
+    value: Option<String>,
+    children: Vec<FuzzNode>,
+    props: Option<Vec<(String, String)>>,
+    raw: bool,
+}
+
+impl From<FuzzNode> for HtmlNode {
+    fn from(node: FuzzNode) -> HtmlNode {
+        HtmlNode {
+            tag: node.tag,
+            value: node.value,
+            children: node.children.into_iter().map(|c| Box::new(HtmlNode::from(c))).collect(),
+            props: node.props.map(Attributes::from_iter),
+            kind: if node.raw { NodeKind::Raw } else { NodeKind::Text },
+        }
+    }
+}
+
+fuzz_target!(|node: FuzzNode| {
+    let _ = HtmlNode::from(node).to_html();
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_static_site::blocks::markdown_to_html_node;
+
+fuzz_target!(|markdown: &str| {
+    let _ = markdown_to_html_node(markdown).to_html();
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_static_site::parse::parse_html;
+
+fuzz_target!(|html: &str| {
+    let _ = parse_html(html).to_html();
+});
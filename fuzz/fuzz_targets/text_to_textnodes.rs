@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_static_site::htmlnode::HtmlNode;
+use rust_static_site::utils::text_to_textnodes;
+
+fuzz_target!(|text: &str| {
+    if let Ok(nodes) = text_to_textnodes(text) {
+        for node in nodes {
+            let _ = HtmlNode::from(node).to_html();
+        }
+    }
+});
@@ -1,7 +1,92 @@
-use crate::htmlnode::HtmlNode;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::attributes::Attributes;
+use crate::htmlnode::{is_block_element, HtmlNode};
+use crate::highlight::{self, HighlightOptions};
+use crate::links::LinkPolicy;
+use crate::math::latex_to_mathml;
+use crate::parse::parse_html;
+use crate::sanitize::SanitizePolicy;
+use crate::slug::Slugger;
+use crate::textnode::{TextNode, TextType};
+use crate::toc::{build_toc, toc_to_html_node, Heading};
 use crate::utils::*;
 
+static HEADING_ID_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\s*\{#([^\s{}]+)\}$").expect("heading id regex is valid")
+});
+
+static CALLOUT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^>\s*\[!(note|tip|important|warning|caution)\]\s*$").expect("callout regex is valid")
+});
+
+static CONTAINER_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^:::\s*([A-Za-z][\w-]*)\s*(.*)$").expect("container regex is valid")
+});
+
+/// Knobs for `markdown_to_html_node_with_options`.
+#[derive(Debug, Clone)]
+pub struct MarkdownOptions {
+    /// Append a self-link (`<a class="anchor" href="#id">#</a>`) to every heading.
+    pub heading_anchors: bool,
+    /// Shallowest heading level listed in a table of contents.
+    pub toc_min_level: u8,
+    /// Deepest heading level listed in a table of contents.
+    pub toc_max_level: u8,
+    /// Turn a paragraph holding nothing but an image into a `figure`, captioned
+    /// with the image's title or alt text.
+    pub figures: bool,
+    /// Colour fenced code blocks whose language is known; `None` leaves all
+    /// code plain.
+    pub highlight: Option<HighlightOptions>,
+    /// Applied to the finished tree, including raw HTML blocks.
+    pub sanitize: SanitizePolicy,
+    /// Decorates links to other sites, after sanitizing.
+    pub links: LinkPolicy,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        MarkdownOptions {
+            heading_anchors: false,
+            toc_min_level: 2,
+            toc_max_level: 6,
+            figures: true,
+            highlight: Some(HighlightOptions::default()),
+            sanitize: SanitizePolicy::default(),
+            links: LinkPolicy::default(),
+        }
+    }
+}
+
+/// A rendered page along with the outline of its headings.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    pub content: HtmlNode,
+    pub headings: Vec<Heading>,
+    /// URLs the sanitizer removed from the page.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub removed_urls: Vec<String>,
+    /// Shallowest heading level listed by `toc`, from `MarkdownOptions`.
+    pub toc_min_level: u8,
+    /// Deepest heading level listed by `toc`, from `MarkdownOptions`.
+    pub toc_max_level: u8,
+}
+
+impl Document {
+    /// The page's table of contents as a nested `ul`, or `None` if no
+    /// heading falls within its TOC levels.
+    pub fn toc(&self) -> Option<HtmlNode> {
+        toc_to_html_node(&build_toc(&self.headings, self.toc_min_level, self.toc_max_level))
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum BlockType {
     Paragraph,
     Heading,
@@ -9,26 +94,77 @@ pub enum BlockType {
     Quote,
     UnorderedList,
     OrderedList,
+    DefinitionList,
+    Admonition,
+    Html,
+    Toc,
+    Math,
+}
+
+/// A block of raw HTML: it opens with a comment or a block-level tag such as
+/// `<div>` or `<table>`, and ends with a `>`.
+pub fn is_html_block(block: &str) -> bool {
+    let Some(rest) = block.strip_prefix('<') else {
+        return false;
+    };
+    if !block.trim_end().ends_with('>') {
+        return false;
+    }
+    if rest.starts_with("!--") {
+        return true;
+    }
+    let name: String = rest.chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+    !name.is_empty() && is_block_element(&name)
+}
+
+/// Either a GitHub-style `> [!NOTE]` quote or a `:::note` ... `:::` container.
+pub fn is_admonition(block: &str) -> bool {
+    let first_line = block.lines().next().unwrap_or_default();
+    if CALLOUT_RE.is_match(first_line) {
+        return is_quote(block);
+    }
+    CONTAINER_RE.is_match(first_line) && block.lines().count() > 1 && block.lines().last() == Some(":::")
+}
+
+pub fn is_toc(block: &str) -> bool {
+    block.trim() == "[TOC]"
 }
 
 pub fn is_quote(block: &str) -> bool {
-    block.lines().into_iter()
+    block.lines()
         .all(|l| l.starts_with(">"))
 }
 
 pub fn is_ul(block: &str) -> bool {
-    block.lines().into_iter()
+    block.lines()
         .all(|l| l.starts_with("- "))
 }
 
 pub fn is_ol(block: &str) -> bool {
-    block.lines().enumerate().into_iter()
+    block.lines().enumerate()
         .all(|(i, line)| {
             let pat = format!("{}. ", i+1);
             line.starts_with(&pat)
         })
 }
 
+/// A definition list is one or more terms, each followed by `: definition` lines.
+/// Every term must be a single line directly followed by a definition, so prose
+/// that happens to start a line with `: ` stays a paragraph.
+pub fn is_definition_list(block: &str) -> bool {
+    let mut previous: Option<&str> = None;
+    for line in block.lines() {
+        let is_term = !line.starts_with(": ");
+        match previous {
+            None if !is_term || line.trim().is_empty() => return false,
+            Some(previous) if is_term && !previous.starts_with(": ") => return false,
+            _ => {},
+        }
+        previous = Some(line);
+    }
+    previous.is_some_and(|last| last.starts_with(": "))
+}
+
 pub fn is_heading(block: &str) -> bool {
     if !block.starts_with("#") {
         return false;
@@ -49,21 +185,43 @@ pub fn is_code(block: &str) -> bool {
         return false;
     }
 
-    if let Some(first_line) = lines.next() {
-        if let Some(last_line) = lines.last() {
-            return first_line == "```" && last_line == "```";
-        }
+    if let (Some(first_line), Some(last_line)) = (lines.next(), lines.last()) {
+        return first_line.starts_with("```") && !first_line[3..].contains('`') && last_line == "```";
     }
-    return false;
+    false
+}
+
+/// A display formula on its own, between `$$` lines or on one line:
+/// `$$E = mc^2$$`.
+pub fn is_math_block(block: &str) -> bool {
+    extract_math_content(block).is_some()
+}
+
+/// The TeX inside a display math block.
+pub fn extract_math_content(block: &str) -> Option<&str> {
+    let tex = block.trim().strip_prefix("$$")?.strip_suffix("$$")?;
+    (!tex.trim().is_empty() && !tex.contains("$$")).then_some(tex.trim())
 }
 
 pub fn block_to_blocktype(block: &str) -> BlockType {
+    if is_toc(block) {
+        return BlockType::Toc;
+    }
     if is_heading(block) {
         return BlockType::Heading;
     }
+    if is_html_block(block) {
+        return BlockType::Html;
+    }
     if is_code(block) {
         return BlockType::Code;
     }
+    if is_math_block(block) {
+        return BlockType::Math;
+    }
+    if is_admonition(block) {
+        return BlockType::Admonition;
+    }
     if is_quote(block) {
         return BlockType::Quote;
     }
@@ -73,33 +231,71 @@ pub fn block_to_blocktype(block: &str) -> BlockType {
     if is_ul(block) {
         return BlockType::UnorderedList;
     }
+    if is_definition_list(block) {
+        return BlockType::DefinitionList;
+    }
 
     BlockType::Paragraph
 }
 
 pub fn markdown_to_blocks(markdown: &str) -> Vec<String> {
-    markdown.split("\n\n")
-        .filter(|s| !s.is_empty())
-        .map(|s| s.trim_end().to_string())
-        .collect()
+    let pieces: Vec<&str> = markdown.split("\n\n").map(|s| s.trim_end()).filter(|s| !s.is_empty()).collect();
+    let opens = |s: &str| CONTAINER_RE.is_match(s.lines().next().unwrap_or_default());
+    let closes = |s: &str| s.lines().last() == Some(":::");
+    let mut blocks: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < pieces.len() {
+        let mut block = pieces[i].to_string();
+        // a `:::` container keeps its blank lines until the closing fence; one
+        // that is never closed is an ordinary block
+        let unclosed = opens(pieces[i]) && !(closes(pieces[i]) && pieces[i].lines().count() > 1);
+        let fence = pieces[i + 1..].iter()
+            .position(|s| opens(s) || closes(s))
+            .filter(|&end| !opens(pieces[i + 1 + end]));
+        if unclosed && let Some(end) = fence {
+            for piece in &pieces[i + 1..=i + 1 + end] {
+                block.push_str("\n\n");
+                block.push_str(piece);
+            }
+            i += end + 1;
+        }
+        blocks.push(block);
+        i += 1;
+    }
+    blocks
 }
 
 pub fn extract_code_content(block: &str) -> String {
-    //block.split("\n").filter(|l| !l.starts_with("```")).collect::<Vec<_>>().join(" ")
-    block.trim_start_matches("```\n")
+    block.split_once('\n')
+        .map_or("", |(_, rest)| rest)
         .trim_end_matches("```")
         .to_string()
 }
 
+/// The language named after the opening fence (` ```rust `), if any.
+pub fn extract_code_language(block: &str) -> Option<String> {
+    block.lines()
+        .next()
+        .and_then(|line| line.strip_prefix("```"))
+        .and_then(|info| info.split_whitespace().next())
+        .map(|language| language.to_string())
+}
+
 pub fn extract_heading_content(block: &str) -> String {
-    block.trim_start_matches("#")
-        .trim_start()
-        .to_string()
+    let content = block.trim_start_matches("#").trim_start();
+    HEADING_ID_RE.replace(content, "").to_string()
+}
+
+/// Returns the explicit id from a trailing `{#custom-id}` on a heading, if any.
+pub fn extract_heading_id(block: &str) -> Option<String> {
+    HEADING_ID_RE.captures(block)
+        .and_then(|cap| cap.get(1))
+        .map(|m| m.as_str().to_string())
 }
 
 pub fn extract_quote_content(block: &str) -> String {
     println!("Extracting quote content from {:?}...", block);
-    block.lines().into_iter()
+    block.lines()
         .filter(|line| !line.is_empty())
         .map(|line| line.trim_start_matches(">"))
         .map(|line| line.trim_start_matches(" "))
@@ -107,87 +303,260 @@ pub fn extract_quote_content(block: &str) -> String {
         .join(" ")
 }
 
+/// Splits an admonition into its kind (`note`, `warning`, ...), its title and
+/// the markdown of its body.
+pub fn extract_admonition_content(block: &str) -> (String, String, String) {
+    let first_line = block.lines().next().unwrap_or_default();
+    if let Some(cap) = CALLOUT_RE.captures(first_line) {
+        let kind = cap[1].to_lowercase();
+        let body = block.lines()
+            .skip(1)
+            .map(|line| line.trim_start_matches(">").trim_start_matches(" "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        return (kind.clone(), capitalize(&kind), body);
+    }
+
+    let (kind, title) = match CONTAINER_RE.captures(first_line) {
+        Some(cap) => (cap[1].to_lowercase(), cap[2].trim().to_string()),
+        None => ("note".to_string(), String::new()),
+    };
+    let title = if title.is_empty() { capitalize(&kind) } else { title };
+    let mut lines: Vec<_> = block.lines().skip(1).collect();
+    lines.pop();
+    (kind, title, lines.join("\n"))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
 pub fn extract_ul_nodes(block: &str) -> Vec<HtmlNode> {
-    block.lines().into_iter()
+    block.lines()
         .map(|line| line.trim_start_matches("- "))
-        .map(|line| text_to_children(line))
+        .map(text_to_children)
         .map(|child_nodes| HtmlNode::parent_node("li", child_nodes, None))
         .collect()
 }
 
 pub fn extract_ol_nodes(block: &str) -> Vec<HtmlNode> {
-    block.lines().enumerate().into_iter()
+    block.lines().enumerate()
         .map(|(i, line)| {
             let pat = format!("{}. ", i+1);
             line.trim_start_matches(&pat)
         })
-        .map(|line| text_to_children(line))
+        .map(text_to_children)
         .map(|child_nodes| HtmlNode::parent_node("li", child_nodes, None))
         .collect()
 }
 
+pub fn extract_dl_nodes(block: &str) -> Vec<HtmlNode> {
+    block.lines()
+        .map(|line| match line.strip_prefix(": ") {
+            Some(definition) => HtmlNode::parent_node("dd", text_to_children(definition), None),
+            None => HtmlNode::parent_node("dt", text_to_children(line), None),
+        })
+        .collect()
+}
+
 pub fn text_to_children(block: &str) -> Vec<HtmlNode> {
-    let text_nodes = text_to_textnodes(block).unwrap();
+    let text_nodes = text_to_textnodes(block)
+        .unwrap_or_else(|_| vec![TextNode { text: block.to_string(), text_type: TextType::Plain, url: None }]);
     let mut html_nodes = Vec::new();
     for tnode in text_nodes {
         html_nodes.push( HtmlNode::from(tnode) );
     }
+    // an element made only of empty markup (e.g. "``") still needs a child to render
+    if html_nodes.is_empty() {
+        html_nodes.push( HtmlNode::leaf_node(None, "", None) );
+    }
     html_nodes
 }
 
-pub fn get_heading_block_tag(block: &str) -> String {
-    println!("Determing heading block tag from: {}", block);
-    let num = block.chars()
+pub fn get_heading_level(block: &str) -> u8 {
+    block.chars()
         .take_while(|c| *c == '#')
         .count()
-        .min(6);
+        .min(6) as u8
+}
+
+pub fn get_heading_block_tag(block: &str) -> String {
+    println!("Determing heading block tag from: {}", block);
+    let num = get_heading_level(block);
     format!("h{num}")
 }
 
+/// Wraps an image in a `figure`, with a `figcaption` from its title or, failing
+/// that, its alt text.
+fn image_to_figure(image: HtmlNode) -> HtmlNode {
+    let caption = image.get_attr("title")
+        .or(image.get_attr("alt"))
+        .filter(|caption| !caption.trim().is_empty())
+        .map(|caption| HtmlNode::leaf_node(Some("figcaption"), caption, None));
+    HtmlNode::parent_node("figure", std::iter::once(image).chain(caption).collect(), None)
+}
+
 pub fn markdown_to_html_node(markdown: &str) -> HtmlNode {
+    markdown_to_html_node_with_options(markdown, &MarkdownOptions::default())
+}
+
+pub fn markdown_to_html_node_with_options(markdown: &str, options: &MarkdownOptions) -> HtmlNode {
+    markdown_to_document(markdown, options).content
+}
+
+pub fn markdown_to_document(markdown: &str, options: &MarkdownOptions) -> Document {
     let blocks = markdown_to_blocks(markdown);
+    let mut builder = DocumentBuilder { options, slugger: Slugger::new(), headings: Vec::new() };
+    builder.reserve_ids(&blocks);
     let mut nodes = Vec::new();
+    let mut toc_slots = Vec::new();
 
     for block in blocks {
-        let block_type = block_to_blocktype(&block);
-        match block_type {
+        if block_to_blocktype(&block) == BlockType::Toc {
+            // filled in once every heading has been seen
+            toc_slots.push(nodes.len());
+        }
+        nodes.push(builder.block_to_node(&block));
+    }
+
+    let headings = builder.headings;
+    let toc = toc_to_html_node(&build_toc(&headings, options.toc_min_level, options.toc_max_level));
+    for slot in toc_slots.into_iter().rev() {
+        match &toc {
+            Some(toc) => nodes[slot] = toc.clone(),
+            None => { nodes.remove(slot); },
+        }
+    }
+    let mut content = HtmlNode::el("div").children(nodes);
+    let removed_urls = options.sanitize.sanitize(&mut content);
+    options.links.apply(&mut content);
+    Document {
+        content,
+        headings,
+        removed_urls,
+        toc_min_level: options.toc_min_level,
+        toc_max_level: options.toc_max_level,
+    }
+}
+
+/// What the blocks of a page share, including the blocks nested in its
+/// admonitions: heading ids are unique across the whole page.
+struct DocumentBuilder<'a> {
+    options: &'a MarkdownOptions,
+    slugger: Slugger,
+    headings: Vec<Heading>,
+}
+
+impl DocumentBuilder<'_> {
+    /// Reserves every explicit `{#id}`, including those in admonitions, so
+    /// the headings that ask for them get exactly those ids.
+    fn reserve_ids(&mut self, blocks: &[String]) {
+        for block in blocks {
+            match block_to_blocktype(block) {
+                BlockType::Heading => {
+                    if let Some(id) = extract_heading_id(block) {
+                        self.slugger.reserve(&id);
+                    }
+                },
+                BlockType::Admonition => {
+                    let (_, _, body) = extract_admonition_content(block);
+                    self.reserve_ids(&markdown_to_blocks(&body));
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn block_to_node(&mut self, block: &str) -> HtmlNode {
+        let options = self.options;
+        match block_to_blocktype(block) {
             BlockType::Paragraph => {
                 let clean_block = block.replace("\n", " ");
                 let child_nodes = text_to_children(&clean_block);
-                let this_node = HtmlNode::parent_node("p", child_nodes, None);
-                nodes.push(this_node);
+                match <[HtmlNode; 1]>::try_from(child_nodes) {
+                    Ok([image]) if options.figures && image.tag.as_deref() == Some("img") => image_to_figure(image),
+                    Ok(nodes) => HtmlNode::parent_node("p", nodes.into(), None),
+                    Err(nodes) => HtmlNode::parent_node("p", nodes, None),
+                }
             },
             BlockType::Heading => {
-                let tag = get_heading_block_tag(&block);
-                let child_nodes = text_to_children(
-                  extract_heading_content(&block).as_str()  
+                let tag = get_heading_block_tag(block);
+                let mut child_nodes = text_to_children(
+                  extract_heading_content(block).as_str()
                 );
-                let this_node = HtmlNode::parent_node(tag.as_str(), child_nodes, None);
-                nodes.push(this_node);
+                let text: String = child_nodes.iter().map(|c| c.text_content()).collect();
+                let id = match extract_heading_id(block) {
+                    Some(id) => self.slugger.claim(&id),
+                    None => self.slugger.slug(&text),
+                };
+                self.headings.push(Heading { level: get_heading_level(block), id: id.clone(), text });
+                if options.heading_anchors {
+                    let props = Attributes::from([
+                        ("class", "anchor".to_string()),
+                        ("href", format!("#{id}")),
+                        ("aria-hidden", "true".to_string()),
+                    ]);
+                    child_nodes.push(HtmlNode::leaf_node(Some("a"), "#", Some(props)));
+                }
+                let props = Attributes::from([("id", id)]);
+                HtmlNode::parent_node(tag.as_str(), child_nodes, Some(props))
             },
             BlockType::Code => {
-                let content = extract_code_content(&block);
-                let code_node = HtmlNode::leaf_node(Some("code"), &content, None);
-                let pre_node = HtmlNode::parent_node("pre", vec![code_node], None);
-                nodes.push(pre_node);
+                let content = extract_code_content(block);
+                let language = extract_code_language(block);
+                let props = language.as_ref()
+                    .map(|language| Attributes::from([("class", format!("language-{language}"))]));
+                let highlighted = options.highlight.as_ref()
+                    .zip(language.as_deref())
+                    .and_then(|(highlight, language)| highlight::highlight(&content, language, highlight))
+                    .filter(|spans| !spans.is_empty());
+                let code_node = match highlighted {
+                    Some(spans) => HtmlNode::parent_node("code", spans, props),
+                    None => HtmlNode::leaf_node(Some("code"), &content, props),
+                };
+                HtmlNode::parent_node("pre", vec![code_node], None)
             },
             BlockType::Quote => {
-                let quote_content = extract_quote_content(&block);
+                let quote_content = extract_quote_content(block);
                 let child_nodes = text_to_children(&quote_content);
-                let this_node = HtmlNode::parent_node("blockquote", child_nodes, None);
-                nodes.push(this_node);
+                HtmlNode::parent_node("blockquote", child_nodes, None)
             },
             BlockType::UnorderedList => {
-                let li_nodes = extract_ul_nodes(&block);
-                let ul_node = HtmlNode::parent_node("ul", li_nodes, None);
-                nodes.push(ul_node);
+                let li_nodes = extract_ul_nodes(block);
+                HtmlNode::parent_node("ul", li_nodes, None)
             },
             BlockType::OrderedList => {
-                let li_nodes = extract_ol_nodes(&block);
-                let ol_node = HtmlNode::parent_node("ol", li_nodes, None);
-                nodes.push(ol_node);
+                let li_nodes = extract_ol_nodes(block);
+                HtmlNode::parent_node("ol", li_nodes, None)
+            },
+            BlockType::DefinitionList => {
+                let entry_nodes = extract_dl_nodes(block);
+                HtmlNode::parent_node("dl", entry_nodes, None)
+            },
+            BlockType::Admonition => {
+                let (kind, title, body) = extract_admonition_content(block);
+                let title_props = Attributes::from([("class", "admonition-title")]);
+                let mut children = vec![HtmlNode::parent_node("p", text_to_children(&title), Some(title_props))];
+                for block in markdown_to_blocks(&body) {
+                    // only the page itself gets a table of contents
+                    if block_to_blocktype(&block) != BlockType::Toc {
+                        children.push(self.block_to_node(&block));
+                    }
+                }
+                let props = Attributes::from([("class", format!("admonition {kind}"))]);
+                HtmlNode::parent_node("div", children, Some(props))
             },
+            BlockType::Html => parse_html(block),
+            BlockType::Math => {
+                let tex = extract_math_content(block).unwrap_or_default();
+                latex_to_mathml(tex, true)
+            },
+            // a placeholder, replaced by `markdown_to_document`
+            BlockType::Toc => HtmlNode::leaf_node(None, "", None),
         }
     }
-    HtmlNode::parent_node("div", nodes, None)
-}
\ No newline at end of file
+}
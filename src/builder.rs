@@ -0,0 +1,177 @@
+use crate::attributes::Attributes;
+use crate::htmlnode::{HtmlNode, NodeKind};
+
+/// Fluent construction, e.g.
+/// `HtmlNode::el("a").attr("href", "/").class("nav").text("Home")`.
+impl HtmlNode {
+    /// An empty element, ready to have attributes and children added.
+    pub fn el(tag: &str) -> Self {
+        HtmlNode::leaf_node(Some(tag), "", None)
+    }
+
+    /// A plain text node.
+    pub fn text_node(text: &str) -> Self {
+        HtmlNode::leaf_node(None, text, None)
+    }
+
+    pub fn attr(mut self, key: &str, value: impl AsRef<str>) -> Self {
+        self.set_attr(key, value.as_ref());
+        self
+    }
+
+    /// Adds a boolean attribute such as `hidden`.
+    pub fn flag(mut self, key: &str) -> Self {
+        self.props.get_or_insert_with(Attributes::new).insert_bool(key);
+        self
+    }
+
+    pub fn class(mut self, class: &str) -> Self {
+        self.add_class(class);
+        self
+    }
+
+    pub fn id(self, id: &str) -> Self {
+        self.attr("id", id)
+    }
+
+    pub fn child(mut self, child: impl Into<HtmlNode>) -> Self {
+        // once an element has children its own value is no longer used
+        if self.tag.is_some() && self.kind == NodeKind::Text {
+            self.value = None;
+        }
+        self.children.push(Box::new(child.into()));
+        self
+    }
+
+    pub fn children<I>(self, children: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<HtmlNode>,
+    {
+        children.into_iter().fold(self, |node, child| node.child(child))
+    }
+
+    /// Appends a text child.
+    pub fn text(self, text: &str) -> Self {
+        self.child(HtmlNode::text_node(text))
+    }
+}
+
+impl From<&str> for HtmlNode {
+    fn from(text: &str) -> HtmlNode {
+        HtmlNode::text_node(text)
+    }
+}
+
+impl From<String> for HtmlNode {
+    fn from(text: String) -> HtmlNode {
+        HtmlNode::text_node(&text)
+    }
+}
+
+/// Builds an `HtmlNode` from a nested, HTML-like description:
+///
+/// ```
+/// use rust_static_site::html;
+///
+/// let url = "/blog/tom";
+/// let node = html! {
+///     nav(class = "links", "aria-label" = "Posts") [
+///         a(href = url) [ "Tom Bombadil" ],
+///         br,
+///         input(type = "checkbox", checked),
+///         { format!("{} posts", 3) },
+///     ]
+/// };
+/// assert_eq!(
+///     r#"<nav class="links" aria-label="Posts"><a href="/blog/tom">Tom Bombadil</a><br><input type="checkbox" checked>3 posts</nav>"#,
+///     node.to_html().unwrap(),
+/// );
+/// ```
+///
+/// Elements are written `tag(attributes) [children]`, with either part
+/// optional. Attribute names are identifiers or string literals, values are
+/// any expression and a bare name is a boolean attribute. Children are
+/// elements, string literals or `{ expression }` for anything that converts
+/// into an `HtmlNode`. Giving a void element such as `img` children is a
+/// compile error:
+///
+/// ```compile_fail
+/// use rust_static_site::html;
+///
+/// let node = html! { img(src = "/a.png") [ "caption" ] };
+/// ```
+#[macro_export]
+macro_rules! html {
+    ($text:literal) => {
+        $crate::htmlnode::HtmlNode::text_node(&($text).to_string())
+    };
+    ({ $e:expr }) => {
+        $crate::htmlnode::HtmlNode::from($e)
+    };
+    ($tag:ident ( $($attrs:tt)* ) [ $($children:tt)+ ]) => {{
+        $crate::html!(@allow_children $tag);
+        $crate::html!(@children $crate::html!($tag ( $($attrs)* )); $($children)+)
+    }};
+    ($tag:ident ( $($attrs:tt)* ) [ ]) => {
+        $crate::html!($tag ( $($attrs)* ))
+    };
+    ($tag:ident ( $($attrs:tt)* )) => {
+        $crate::html!(@attrs $crate::htmlnode::HtmlNode::el(stringify!($tag)); $($attrs)*)
+    };
+    ($tag:ident [ $($children:tt)* ]) => {
+        $crate::html!($tag () [ $($children)* ])
+    };
+    ($tag:ident) => {
+        $crate::html!($tag ())
+    };
+
+    (@attrs $node:expr; ) => { $node };
+    (@attrs $node:expr; $key:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::html!(@attrs $node.attr(stringify!($key), $value); $($($rest)*)?)
+    };
+    (@attrs $node:expr; $key:literal = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::html!(@attrs $node.attr($key, $value); $($($rest)*)?)
+    };
+    (@attrs $node:expr; $key:ident $(, $($rest:tt)*)?) => {
+        $crate::html!(@attrs $node.flag(stringify!($key)); $($($rest)*)?)
+    };
+    (@attrs $node:expr; $key:literal $(, $($rest:tt)*)?) => {
+        $crate::html!(@attrs $node.flag($key); $($($rest)*)?)
+    };
+
+    (@children $node:expr; ) => { $node };
+    (@children $node:expr; $text:literal $(, $($rest:tt)*)?) => {
+        $crate::html!(@children $node.child($crate::html!($text)); $($($rest)*)?)
+    };
+    (@children $node:expr; { $e:expr } $(, $($rest:tt)*)?) => {
+        $crate::html!(@children $node.child($crate::html!({ $e })); $($($rest)*)?)
+    };
+    (@children $node:expr; $tag:ident ( $($attrs:tt)* ) [ $($children:tt)* ] $(, $($rest:tt)*)?) => {
+        $crate::html!(@children $node.child($crate::html!($tag ( $($attrs)* ) [ $($children)* ])); $($($rest)*)?)
+    };
+    (@children $node:expr; $tag:ident ( $($attrs:tt)* ) $(, $($rest:tt)*)?) => {
+        $crate::html!(@children $node.child($crate::html!($tag ( $($attrs)* ))); $($($rest)*)?)
+    };
+    (@children $node:expr; $tag:ident [ $($children:tt)* ] $(, $($rest:tt)*)?) => {
+        $crate::html!(@children $node.child($crate::html!($tag [ $($children)* ])); $($($rest)*)?)
+    };
+    (@children $node:expr; $tag:ident $(, $($rest:tt)*)?) => {
+        $crate::html!(@children $node.child($crate::html!($tag)); $($($rest)*)?)
+    };
+
+    (@allow_children area) => { compile_error!("<area> is a void element and can't have children") };
+    (@allow_children base) => { compile_error!("<base> is a void element and can't have children") };
+    (@allow_children br) => { compile_error!("<br> is a void element and can't have children") };
+    (@allow_children col) => { compile_error!("<col> is a void element and can't have children") };
+    (@allow_children embed) => { compile_error!("<embed> is a void element and can't have children") };
+    (@allow_children hr) => { compile_error!("<hr> is a void element and can't have children") };
+    (@allow_children img) => { compile_error!("<img> is a void element and can't have children") };
+    (@allow_children input) => { compile_error!("<input> is a void element and can't have children") };
+    (@allow_children link) => { compile_error!("<link> is a void element and can't have children") };
+    (@allow_children meta) => { compile_error!("<meta> is a void element and can't have children") };
+    (@allow_children source) => { compile_error!("<source> is a void element and can't have children") };
+    (@allow_children track) => { compile_error!("<track> is a void element and can't have children") };
+    (@allow_children wbr) => { compile_error!("<wbr> is a void element and can't have children") };
+    (@allow_children $tag:ident) => {};
+}
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::errors::NodeError;
+use crate::htmlnode::HtmlNode;
+use crate::images::site_path;
+use crate::parse::parse_html;
+use crate::sanitize::url_scheme;
+use crate::url::BasePath;
+
+/// What's wrong with a generated site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// A link to a page or file that wasn't generated.
+    BrokenLink,
+    /// An image, stylesheet or other resource that wasn't generated.
+    MissingAsset,
+    /// A link to a `#fragment` that no element on the page has as its `id`.
+    MissingAnchor,
+    /// A page no other page links to.
+    OrphanPage,
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProblemKind::BrokenLink => "broken link",
+            ProblemKind::MissingAsset => "missing asset",
+            ProblemKind::MissingAnchor => "missing anchor",
+            ProblemKind::OrphanPage => "orphan page",
+        })
+    }
+}
+
+/// A problem found by `check_site`, located in the Markdown source of the
+/// page when there is one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    /// The generated page, relative to the output directory.
+    pub page: PathBuf,
+    /// The Markdown file `page` was generated from.
+    pub source: Option<PathBuf>,
+    /// The 1-based line in `source` the URL appears on, if it could be found.
+    /// URLs that come from the template have none.
+    pub line: Option<usize>,
+    /// The URL as written in the page. Empty for orphan pages.
+    pub url: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.source, self.line) {
+            (Some(source), Some(line)) => write!(f, "{}:{line}: ", source.display())?,
+            (Some(source), None) => write!(f, "{}: ", source.display())?,
+            (None, _) => write!(f, "{}: ", self.page.display())?,
+        }
+        match self.kind {
+            ProblemKind::OrphanPage => write!(f, "{}, nothing links to {}", self.kind, self.page.display()),
+            kind => write!(f, "{kind} {:?} in {}", self.url, self.page.display()),
+        }
+    }
+}
+
+/// A URL found in a page.
+struct Reference {
+    url: String,
+    kind: ProblemKind,
+    /// Link text or alt text, to find the URL's line when the source spells it
+    /// differently.
+    text: String,
+}
+
+struct Page {
+    ids: BTreeSet<String>,
+    references: Vec<Reference>,
+}
+
+/// Checks every internal link, asset and `#fragment` in the site generated
+/// in `output_dir`, served under `base`, and looks for orphan pages. Problems
+/// point at the Markdown sources in `content_dir`.
+pub fn check_site(output_dir: &Path, content_dir: &Path, base: &BasePath) -> Result<Vec<Problem>, NodeError> {
+    let mut pages = BTreeMap::new();
+    for path in html_files(output_dir, Path::new(""))? {
+        let html = std::fs::read_to_string(output_dir.join(&path))?;
+        pages.insert(path, read_page(&parse_html(&html)));
+    }
+
+    let mut problems = Vec::new();
+    let mut linked = BTreeSet::new();
+    for (path, page) in &pages {
+        let page_dir = path.parent().unwrap_or(Path::new(""));
+        let mut found = Vec::new();
+        for reference in &page.references {
+            let (url, fragment) = reference.url.split_once('#').unwrap_or((&reference.url, ""));
+            let target = if url.is_empty() {
+                Some(path.clone())
+            } else {
+                resolve(url, page_dir, output_dir, base)
+            };
+            let Some(target) = target else {
+                found.push((reference.kind, reference));
+                continue;
+            };
+            if reference.kind == ProblemKind::BrokenLink && &target != path {
+                linked.insert(target.clone());
+            }
+            if let Some(target_page) = pages.get(&target)
+                && !fragment.is_empty()
+                && fragment != "top"
+                && !target_page.ids.contains(fragment)
+                && !target_page.ids.contains(&percent_decode(fragment))
+            {
+                found.push((ProblemKind::MissingAnchor, reference));
+            }
+        }
+
+        let source = source_file(path, content_dir);
+        let markdown = source.as_ref().and_then(|source| std::fs::read_to_string(source).ok());
+        for (kind, reference) in found {
+            problems.push(Problem {
+                kind,
+                page: path.clone(),
+                source: source.clone(),
+                line: markdown.as_deref().and_then(|markdown| source_line(markdown, reference, base)),
+                url: reference.url.clone(),
+            });
+        }
+    }
+
+    for path in pages.keys().filter(|path| *path != Path::new("index.html") && !linked.contains(*path)) {
+        problems.push(Problem {
+            kind: ProblemKind::OrphanPage,
+            page: path.clone(),
+            source: source_file(path, content_dir),
+            line: None,
+            url: String::new(),
+        });
+    }
+    Ok(problems)
+}
+
+/// The `.html` files under `dir`, relative to the output root, in order.
+fn html_files(root: &Path, dir: &Path) -> Result<Vec<PathBuf>, NodeError> {
+    let mut entries: Vec<_> = std::fs::read_dir(root.join(dir))?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = dir.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            files.extend(html_files(root, &path)?);
+        } else if path.extension().is_some_and(|ext| ext == "html") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn read_page(root: &HtmlNode) -> Page {
+    let mut page = Page { ids: BTreeSet::new(), references: Vec::new() };
+    root.walk(|node| {
+        let Some(tag) = node.tag.as_deref() else {
+            return;
+        };
+        if let Some(id) = node.get_attr("id") {
+            page.ids.insert(id.to_string());
+        }
+        if tag == "a"
+            && let Some(name) = node.get_attr("name")
+        {
+            page.ids.insert(name.to_string());
+        }
+        let mut add = |url: &str, kind| {
+            let seen = page.references.iter().any(|reference| reference.url == url && reference.kind == kind);
+            if !is_external(url) && !seen {
+                let text = match tag {
+                    "img" => node.get_attr("alt").unwrap_or_default().to_string(),
+                    _ => node.text_content(),
+                };
+                page.references.push(Reference { url: url.to_string(), kind, text });
+            }
+        };
+        let link_kind = match tag {
+            "a" | "area" => ProblemKind::BrokenLink,
+            _ => ProblemKind::MissingAsset,
+        };
+        if let Some(href) = node.get_attr("href") {
+            add(href, link_kind);
+        }
+        for key in ["src", "poster"] {
+            if let Some(url) = node.get_attr(key) {
+                add(url, ProblemKind::MissingAsset);
+            }
+        }
+        for key in ["srcset", "imagesrcset"] {
+            for candidate in node.get_attr(key).unwrap_or_default().split(',') {
+                if let Some(url) = candidate.split_whitespace().next() {
+                    add(url, ProblemKind::MissingAsset);
+                }
+            }
+        }
+    });
+    page
+}
+
+fn is_external(url: &str) -> bool {
+    url_scheme(url).is_some() || url.starts_with("//")
+}
+
+/// Decodes `%XX` escapes, as a browser does before looking for the element a
+/// fragment names. Malformed escapes are kept as they are.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = text.get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The generated file `url` is served from, relative to `output_dir`, or
+/// `None` if there isn't one. Directory URLs are served from `index.html`.
+fn resolve(url: &str, page_dir: &Path, output_dir: &Path, base: &BasePath) -> Option<PathBuf> {
+    let url = url.split('?').next().unwrap_or_default();
+    let path = match url.strip_prefix('/') {
+        Some(_) => {
+            let rest = match url.strip_prefix(base.as_str()) {
+                Some(rest) => rest,
+                // the base path itself, without its trailing slash
+                None if format!("{url}/") == base.as_str() => "",
+                None => return None,
+            };
+            site_path(&format!("/{rest}"), page_dir)?
+        },
+        None => site_path(url, page_dir)?,
+    };
+    let file = output_dir.join(&path);
+    if file.is_dir() {
+        let index = path.join("index.html");
+        output_dir.join(&index).is_file().then_some(index)
+    } else {
+        file.is_file().then_some(path)
+    }
+}
+
+/// The Markdown file a page was generated from, if it exists.
+fn source_file(page: &Path, content_dir: &Path) -> Option<PathBuf> {
+    Some(content_dir.join(page.with_extension("md"))).filter(|source| source.is_file())
+}
+
+/// The first line of `markdown` mentioning the reference, looking for the
+/// URL as written, without the base path, and finally its text.
+fn source_line(markdown: &str, reference: &Reference, base: &BasePath) -> Option<usize> {
+    let unbased = reference.url.strip_prefix(base.as_str()).map(|rest| format!("/{rest}"));
+    let text = reference.text.trim();
+    let needles = [Some(format!("({}", reference.url)), unbased.map(|url| format!("({url}")), Some(text.to_string())];
+    needles.iter()
+        .flatten()
+        .filter(|needle| !needle.is_empty())
+        .find_map(|needle| markdown.lines().position(|line| line.contains(needle.as_str())))
+        .map(|index| index + 1)
+}
@@ -3,7 +3,9 @@ pub enum NodeError {
     ValueError(String),
     ParseError(String),
     IoError(std::io::Error),
+    FmtError(std::fmt::Error),
     RegexError(regex::Error),
+    ImageError(image::ImageError),
 }
 
 impl From<std::io::Error> for NodeError {
@@ -16,4 +18,15 @@ impl From<regex::Error> for NodeError {
     fn from(err: regex::Error) -> Self {
         NodeError::RegexError(err)
     }
-}
\ No newline at end of file
+}
+
+impl From<std::fmt::Error> for NodeError {
+    fn from(err: std::fmt::Error) -> Self {
+        NodeError::FmtError(err)
+    }
+}
+impl From<image::ImageError> for NodeError {
+    fn from(err: image::ImageError) -> Self {
+        NodeError::ImageError(err)
+    }
+}
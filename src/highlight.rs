@@ -0,0 +1,447 @@
+use crate::attributes::Attributes;
+use crate::htmlnode::HtmlNode;
+
+/// The kinds of token the highlighter colours. Everything else is plain text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Comment,
+    String,
+    Number,
+    Keyword,
+    /// `true`, `false`, `None`, `null` and friends.
+    Literal,
+    Type,
+    /// A name that is called, including Rust macros.
+    Function,
+    /// Object keys, HTML attribute names and CSS properties.
+    Attribute,
+    /// HTML and XML tag names.
+    Tag,
+}
+
+impl TokenKind {
+    const ALL: [TokenKind; 9] = [
+        TokenKind::Comment,
+        TokenKind::String,
+        TokenKind::Number,
+        TokenKind::Keyword,
+        TokenKind::Literal,
+        TokenKind::Type,
+        TokenKind::Function,
+        TokenKind::Attribute,
+        TokenKind::Tag,
+    ];
+
+    /// The class of the `span` wrapped around tokens of this kind.
+    pub fn class(self) -> &'static str {
+        match self {
+            TokenKind::Comment => "hl-comment",
+            TokenKind::String => "hl-string",
+            TokenKind::Number => "hl-number",
+            TokenKind::Keyword => "hl-keyword",
+            TokenKind::Literal => "hl-literal",
+            TokenKind::Type => "hl-type",
+            TokenKind::Function => "hl-function",
+            TokenKind::Attribute => "hl-attribute",
+            TokenKind::Tag => "hl-tag",
+        }
+    }
+}
+
+/// How highlighted tokens are marked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HighlightMode {
+    /// `<span class="hl-keyword">`, styled by the CSS from `Theme::css`.
+    #[default]
+    Classes,
+    /// `<span style="color: ...">`, for pages that can't load a stylesheet.
+    /// Note that `SanitizePolicy::strict` removes `style` attributes.
+    InlineStyles,
+}
+
+/// Colours for each kind of token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub styles: Vec<(TokenKind, String)>,
+}
+
+impl Default for Theme {
+    /// Warm colours to go with the site's dark stylesheet.
+    fn default() -> Self {
+        let styles = [
+            (TokenKind::Comment, "color: #8a8494; font-style: italic"),
+            (TokenKind::String, "color: #a7c080"),
+            (TokenKind::Number, "color: #e9b872"),
+            (TokenKind::Keyword, "color: #dda15e; font-weight: bold"),
+            (TokenKind::Literal, "color: #e9b872"),
+            (TokenKind::Type, "color: #7fbbb3"),
+            (TokenKind::Function, "color: #83a598"),
+            (TokenKind::Attribute, "color: #d3869b"),
+            (TokenKind::Tag, "color: #e76f51"),
+        ];
+        Theme { styles: styles.into_iter().map(|(kind, style)| (kind, style.to_string())).collect() }
+    }
+}
+
+impl Theme {
+    pub fn style(&self, kind: TokenKind) -> Option<&str> {
+        self.styles.iter().find(|(k, _)| *k == kind).map(|(_, style)| style.as_str())
+    }
+
+    /// A stylesheet for `HighlightMode::Classes`.
+    pub fn css(&self) -> String {
+        TokenKind::ALL.iter()
+            .filter_map(|&kind| Some(format!(".{} {{ {}; }}\n", kind.class(), self.style(kind)?)))
+            .collect()
+    }
+}
+
+/// Settings for highlighting fenced code blocks.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HighlightOptions {
+    pub mode: HighlightMode,
+    pub theme: Theme,
+}
+
+/// What the lexer needs to know about a language.
+struct Language {
+    names: &'static [&'static str],
+    keywords: &'static [&'static str],
+    literals: &'static [&'static str],
+    types: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Python-style `"""` strings.
+    triple_quotes: bool,
+    /// Identifiers starting with a capital letter are types.
+    capitalized_types: bool,
+    /// `'a` is a lifetime unless it closes like a char literal.
+    lifetimes: bool,
+    /// Characters allowed in identifiers besides letters, digits and `_`.
+    ident_chars: &'static [char],
+    /// A string or identifier followed by `:` is a key.
+    colon_keys: bool,
+}
+
+const PLAIN: Language = Language {
+    names: &[],
+    keywords: &[],
+    literals: &[],
+    types: &[],
+    line_comments: &[],
+    block_comment: None,
+    quotes: &['"', '\''],
+    triple_quotes: false,
+    capitalized_types: false,
+    lifetimes: false,
+    ident_chars: &[],
+    colon_keys: false,
+};
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        names: &["rust", "rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+            "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+            "return", "self", "Self", "static", "struct", "super", "trait", "type", "unsafe", "use",
+            "where", "while",
+        ],
+        literals: &["true", "false", "None"],
+        types: &[
+            "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+            "i64", "i128", "isize", "f32", "f64",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        capitalized_types: true,
+        lifetimes: true,
+        ..PLAIN
+    },
+    Language {
+        names: &["python", "py"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+            "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda",
+            "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+        ],
+        literals: &["True", "False", "None"],
+        types: &["int", "float", "str", "bool", "list", "dict", "set", "tuple", "bytes"],
+        line_comments: &["#"],
+        triple_quotes: true,
+        capitalized_types: true,
+        ..PLAIN
+    },
+    Language {
+        names: &["javascript", "js", "jsx", "typescript", "ts", "tsx", "mjs"],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete",
+            "do", "else", "export", "extends", "finally", "for", "from", "function", "if", "import", "in",
+            "instanceof", "interface", "let", "new", "of", "return", "static", "switch", "this", "throw",
+            "try", "type", "typeof", "var", "void", "while", "yield",
+        ],
+        literals: &["true", "false", "null", "undefined", "NaN"],
+        types: &["string", "number", "boolean", "any", "unknown", "never"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        capitalized_types: true,
+        ident_chars: &['$'],
+        ..PLAIN
+    },
+    Language {
+        names: &["c", "h", "cpp", "c++", "cc", "hpp", "java", "go", "golang", "cs", "csharp"],
+        keywords: &[
+            "break", "case", "catch", "class", "const", "continue", "default", "defer", "do", "else",
+            "enum", "extends", "extern", "final", "for", "func", "go", "goto", "if", "implements", "import",
+            "interface", "namespace", "new", "package", "private", "protected", "public", "range",
+            "return", "sizeof", "static", "struct", "switch", "template", "this", "throw", "try",
+            "typedef", "union", "using", "var", "virtual", "volatile", "while",
+        ],
+        literals: &["true", "false", "null", "nullptr", "nil", "NULL"],
+        types: &[
+            "bool", "byte", "char", "double", "float", "int", "long", "short", "signed", "string",
+            "unsigned", "void", "auto", "error",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        capitalized_types: true,
+        ..PLAIN
+    },
+    Language {
+        names: &["json", "jsonc"],
+        literals: &["true", "false", "null"],
+        line_comments: &["//"],
+        quotes: &['"'],
+        colon_keys: true,
+        ..PLAIN
+    },
+    Language {
+        names: &["toml", "ini", "yaml", "yml"],
+        literals: &["true", "false", "null", "yes", "no"],
+        line_comments: &["#", ";"],
+        ident_chars: &['-', '.'],
+        colon_keys: true,
+        ..PLAIN
+    },
+    Language {
+        names: &["bash", "sh", "shell", "zsh", "console"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in",
+            "local", "return", "then", "until", "while",
+        ],
+        literals: &["true", "false"],
+        line_comments: &["#"],
+        ident_chars: &['-'],
+        ..PLAIN
+    },
+    Language {
+        names: &["css", "scss"],
+        keywords: &["!important", "@media", "@import", "@font-face", "@keyframes", "@supports"],
+        line_comments: &[],
+        block_comment: Some(("/*", "*/")),
+        ident_chars: &['-', '@', '!'],
+        colon_keys: true,
+        ..PLAIN
+    },
+];
+
+/// Markup languages get their own small lexer.
+const MARKUP: &[&str] = &["html", "xml", "svg", "xhtml"];
+
+/// Whether `language` (a fenced block's info string) is highlighted.
+pub fn is_supported(language: &str) -> bool {
+    let language = language.to_ascii_lowercase();
+    MARKUP.contains(&language.as_str()) || find_language(&language).is_some()
+}
+
+fn find_language(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|language| language.names.contains(&name))
+}
+
+/// Splits `code` into tokens, with `None` for plain text. Returns `None` for
+/// languages the highlighter doesn't know.
+pub fn tokenize<'a>(code: &'a str, language: &str) -> Option<Vec<(Option<TokenKind>, &'a str)>> {
+    let language = language.to_ascii_lowercase();
+    let tokens = if MARKUP.contains(&language.as_str()) {
+        tokenize_markup(code)
+    } else {
+        tokenize_code(code, find_language(&language)?)
+    };
+    // merge neighbouring tokens of the same kind
+    let mut merged = Tokens::new();
+    for (kind, range) in tokens.into_iter().filter(|(_, range)| !range.is_empty()) {
+        match merged.last_mut() {
+            Some((last_kind, last)) if *last_kind == kind => last.end = range.end,
+            _ => merged.push((kind, range)),
+        }
+    }
+    Some(merged.into_iter().map(|(kind, range)| (kind, &code[range])).collect())
+}
+
+type Tokens = Vec<(Option<TokenKind>, std::ops::Range<usize>)>;
+
+/// The byte index just past `pattern` in `code` at or after `from`, or the
+/// end of `code` when it's missing.
+fn end_of(code: &str, from: usize, pattern: &str) -> usize {
+    code[from..].find(pattern).map_or(code.len(), |i| from + i + pattern.len())
+}
+
+/// The end of a string starting with `quote` at `start`, honouring backslash
+/// escapes.
+fn end_of_string(code: &str, start: usize, quote: &str) -> usize {
+    let mut chars = code[start + quote.len()..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if code[start + quote.len() + i..].starts_with(quote) {
+            return start + quote.len() + i + quote.len();
+        } else if c == '\n' && quote.len() == 1 && quote != "`" {
+            // an unterminated string stops at the end of the line
+            return start + quote.len() + i;
+        }
+    }
+    code.len()
+}
+
+fn next_char_is(code: &str, at: usize, wanted: char) -> bool {
+    code[at..].trim_start_matches([' ', '\t']).starts_with(wanted)
+}
+
+fn tokenize_code(code: &str, language: &Language) -> Tokens {
+    let is_ident_start = |c: char| c.is_alphabetic() || c == '_' || language.ident_chars.contains(&c);
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || language.ident_chars.contains(&c);
+    let mut tokens = Tokens::new();
+    let mut i = 0;
+    while let Some(c) = code[i..].chars().next() {
+        let rest = &code[i..];
+        let (kind, end) = if let Some(prefix) = language.line_comments.iter().find(|p| rest.starts_with(**p)) {
+            let end = code[i + prefix.len()..].find('\n').map_or(code.len(), |n| i + prefix.len() + n);
+            (Some(TokenKind::Comment), end)
+        } else if let Some((open, close)) = language.block_comment.filter(|(open, _)| rest.starts_with(open)) {
+            (Some(TokenKind::Comment), end_of(code, i + open.len(), close))
+        } else if language.lifetimes && c == '\'' && !is_char_literal(rest) {
+            let len = rest[1..].find(|c: char| !is_ident(c)).map_or(rest.len(), |n| n + 1);
+            (Some(TokenKind::Type), i + len)
+        } else if language.quotes.contains(&c) {
+            let quote = if language.triple_quotes && rest.starts_with(&c.to_string().repeat(3)) {
+                &rest[..3]
+            } else {
+                &rest[..c.len_utf8()]
+            };
+            let end = end_of_string(code, i, quote);
+            let kind = if language.colon_keys && next_char_is(code, end, ':') { TokenKind::Attribute } else { TokenKind::String };
+            (Some(kind), end)
+        } else if c.is_ascii_digit() || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit())) {
+            let len = rest[1..].find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.')).map_or(rest.len(), |n| n + 1);
+            (Some(TokenKind::Number), i + len)
+        } else if is_ident_start(c) {
+            let len = rest.find(|c: char| !is_ident(c)).unwrap_or(rest.len());
+            let word = &rest[..len];
+            let kind = if language.keywords.contains(&word) {
+                Some(TokenKind::Keyword)
+            } else if language.literals.contains(&word) {
+                Some(TokenKind::Literal)
+            } else if language.types.contains(&word)
+                || (language.capitalized_types && word.starts_with(|c: char| c.is_uppercase())) {
+                Some(TokenKind::Type)
+            } else if language.colon_keys && is_key_colon(&rest[len..]) {
+                Some(TokenKind::Attribute)
+            } else if next_char_is(code, i + len, '(') || (language.lifetimes && rest[len..].starts_with("!(")) {
+                Some(TokenKind::Function)
+            } else {
+                None
+            };
+            (kind, i + len)
+        } else {
+            (None, i + c.len_utf8())
+        };
+        let end = end.max(i + c.len_utf8());
+        tokens.push((kind, i..end));
+        i = end;
+    }
+    tokens
+}
+
+/// A `:` followed by whitespace, as in `color: red` but not `a:hover`.
+fn is_key_colon(rest: &str) -> bool {
+    rest.trim_start_matches([' ', '\t'])
+        .strip_prefix(':')
+        .is_some_and(|after| after.is_empty() || after.starts_with(char::is_whitespace))
+}
+
+/// `'a'`, `'\n'` or `'\u{1F600}'` rather than the lifetime `'a`.
+fn is_char_literal(rest: &str) -> bool {
+    let mut chars = rest[1..].chars();
+    match chars.next() {
+        Some('\\') => rest[2..].find('\'').is_some_and(|end| end < 10),
+        Some(_) => chars.next() == Some('\''),
+        None => false,
+    }
+}
+
+fn tokenize_markup(code: &str) -> Tokens {
+    let mut tokens = Tokens::new();
+    let mut i = 0;
+    while i < code.len() {
+        let rest = &code[i..];
+        if rest.starts_with("<!--") {
+            let end = end_of(code, i + 4, "-->");
+            tokens.push((Some(TokenKind::Comment), i..end));
+            i = end;
+        } else if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!' || c == '?') {
+            let name_start = i + 1 + usize::from(rest[1..].starts_with(['/', '!', '?']));
+            tokens.push((None, i..name_start));
+            let name_len = code[name_start..].find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(code.len() - name_start);
+            tokens.push((Some(TokenKind::Tag), name_start..name_start + name_len));
+            i = name_start + name_len;
+            // attributes up to the end of the tag
+            while let Some(c) = code[i..].chars().next() {
+                if c == '>' {
+                    tokens.push((None, i..i + 1));
+                    i += 1;
+                    break;
+                } else if c == '"' || c == '\'' {
+                    let end = end_of(code, i + 1, &c.to_string());
+                    tokens.push((Some(TokenKind::String), i..end));
+                    i = end;
+                } else if c.is_alphabetic() {
+                    let len = code[i..].find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '/')).unwrap_or(code.len() - i);
+                    tokens.push((Some(TokenKind::Attribute), i..i + len));
+                    i += len;
+                } else {
+                    tokens.push((None, i..i + c.len_utf8()));
+                    i += c.len_utf8();
+                }
+            }
+        } else {
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let len = rest[first..].find('<').map_or(rest.len(), |n| n + first);
+            tokens.push((None, i..i + len));
+            i += len;
+        }
+    }
+    tokens
+}
+
+/// The children of a `code` element for `code` in `language`, or `None` when
+/// the language isn't supported.
+pub fn highlight(code: &str, language: &str, options: &HighlightOptions) -> Option<Vec<HtmlNode>> {
+    let tokens = tokenize(code, language)?;
+    let nodes = tokens.into_iter()
+        .map(|(kind, text)| match kind {
+            None => HtmlNode::leaf_node(None, text, None),
+            Some(kind) => {
+                let props = match options.mode {
+                    HighlightMode::Classes => Attributes::from([("class", kind.class())]),
+                    HighlightMode::InlineStyles => Attributes::from([("style", options.theme.style(kind).unwrap_or_default())]),
+                };
+                HtmlNode::leaf_node(Some("span"), text, Some(props))
+            },
+        })
+        .collect();
+    Some(nodes)
+}
@@ -1,81 +1,448 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use crate::attributes::Attributes;
 use crate::errors::NodeError;
 
-#[derive(Debug)]
+/// Elements whose text content is not HTML-decoded by browsers, so escaping
+/// it would corrupt the script or stylesheet.
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+/// Elements that never have content and are written without a closing tag.
+pub const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+pub fn is_void_element(tag: &str) -> bool {
+    VOID_ELEMENTS.contains(&tag.to_ascii_lowercase().as_str())
+}
+
+/// Elements whose whitespace is significant, so the pretty printer and
+/// minifier leave their contents exactly as they are.
+const PREFORMATTED_ELEMENTS: [&str; 5] = ["pre", "code", "textarea", "script", "style"];
+
+/// Elements the pretty printer puts on their own line.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "html", "head", "body", "title", "meta", "link", "script", "style",
+    "article", "aside", "blockquote", "details", "div", "dl", "dt", "dd", "fieldset", "figcaption", "figure",
+    "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "main", "nav", "ol", "p", "pre",
+    "section", "table", "tbody", "td", "th", "thead", "tr", "ul",
+];
+
+pub fn is_block_element(tag: &str) -> bool {
+    BLOCK_ELEMENTS.contains(&tag.to_ascii_lowercase().as_str())
+}
+
+/// Layout of the serialized HTML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Everything on one line, text written as-is.
+    #[default]
+    Compact,
+    /// Block elements on their own lines, indented by nesting depth.
+    Pretty,
+    /// Whitespace runs collapsed and attribute quotes dropped where possible.
+    Minified,
+}
+
+/// Settings for `HtmlNode::to_html_with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct HtmlOptions {
+    /// Close void elements XHTML-style (`<br />`) instead of `<br>`.
+    pub xhtml: bool,
+    pub format: OutputFormat,
+}
+
+/// Where the serializer currently is in the tree.
+#[derive(Debug, Clone, Copy, Default)]
+struct Context {
+    /// Inside `script` or `style`: text is written unescaped.
+    raw_text: bool,
+    /// Inside `pre` and friends: whitespace is left alone.
+    preformatted: bool,
+    depth: usize,
+}
+
+/// How a node's `value` is written by `to_html`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum NodeKind {
+    /// Text that is escaped for the context it appears in.
+    #[default]
+    Text,
+    /// Trusted HTML written out verbatim.
+    Raw,
+    /// An HTML comment; `value` is the text between `<!--` and `-->`.
+    Comment,
+    /// A document type declaration; `value` is the name, e.g. `html`.
+    Doctype,
+    /// A tagless container whose children are written one after another,
+    /// such as a whole parsed document.
+    Fragment,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HtmlNode {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
     pub tag: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
     pub value: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
     pub children: Vec<Box<HtmlNode>>,
-    pub props: Option<HashMap<String, String>>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub props: Option<Attributes>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_text_kind"))]
+    pub kind: NodeKind,
+}
+
+#[cfg(feature = "serde")]
+fn is_text_kind(kind: &NodeKind) -> bool {
+    *kind == NodeKind::Text
+}
+
+/// Writes `text` with `&`, `<` and `>` escaped, plus `"` when `in_attr` is set.
+fn write_escaped<W: fmt::Write>(out: &mut W, text: &str, in_attr: bool) -> fmt::Result {
+    let mut last = 0;
+    for (i, c) in text.char_indices() {
+        let entity = match c {
+            '&' => "&amp;",
+            '<' => "&lt;",
+            '>' => "&gt;",
+            '"' if in_attr => "&quot;",
+            _ => continue,
+        };
+        out.write_str(&text[last..i])?;
+        out.write_str(entity)?;
+        last = i + c.len_utf8();
+    }
+    out.write_str(&text[last..])
+}
+
+/// Writes escaped text with every run of whitespace collapsed to one space.
+fn write_collapsed<W: fmt::Write>(out: &mut W, text: &str) -> fmt::Result {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                collapsed.push(' ');
+            }
+            in_space = true;
+        } else {
+            collapsed.push(c);
+            in_space = false;
+        }
+    }
+    write_escaped(out, &collapsed, false)
+}
+
+/// Attribute values made only of these characters can go unquoted.
+fn is_unquotable(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:#".contains(c))
+}
+
+/// Escapes `&`, `<` and `>` so text can't be mistaken for markup.
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    // writing into a String never fails
+    let _ = write_escaped(&mut escaped, text, false);
+    escaped
+}
+
+/// Escapes a value for use inside a double-quoted attribute.
+pub fn escape_attr(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let _ = write_escaped(&mut escaped, value, true);
+    escaped
+}
+
+/// Lets the `fmt::Write` serializer stream into an `io::Write`, keeping the
+/// underlying I/O error that `fmt::Error` can't carry.
+struct IoAdapter<'a, W: io::Write> {
+    inner: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoAdapter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
 }
 
 impl HtmlNode {
 
-    pub fn parent_node(tag: &str, children: Vec<HtmlNode>, props: Option<HashMap<String, String>>) -> Self {
+    pub fn parent_node(tag: &str, children: Vec<HtmlNode>, props: Option<Attributes>) -> Self {
         HtmlNode {
             tag: Some(tag.to_string()),
             value: None,
-            children: children.into_iter().map(|c| Box::new(c)).collect(),
+            children: children.into_iter().map(Box::new).collect(),
             props,
+            kind: NodeKind::Text,
         }
     }
 
-    pub fn leaf_node(tag: Option<&str>, value: &str, props: Option<HashMap<String, String>>) -> Self {
-        let tag = match tag {
-            None => None,
-            Some(str) => Some(str.to_string())
-        };
+    pub fn leaf_node(tag: Option<&str>, value: &str, props: Option<Attributes>) -> Self {
         HtmlNode {
-            tag,
+            tag: tag.map(str::to_string),
             value: Some(value.to_string()),
             children: Vec::new(),
             props,
+            kind: NodeKind::Text,
+        }
+    }
+
+    /// An element such as `img` or `br` that has attributes but no content.
+    pub fn void_node(tag: &str, props: Option<Attributes>) -> Self {
+        HtmlNode {
+            tag: Some(tag.to_string()),
+            value: None,
+            children: Vec::new(),
+            props,
+            kind: NodeKind::Text,
+        }
+    }
+
+    pub fn comment_node(text: &str) -> Self {
+        HtmlNode {
+            kind: NodeKind::Comment,
+            ..HtmlNode::leaf_node(None, text, None)
+        }
+    }
+
+    pub fn doctype_node(name: &str) -> Self {
+        HtmlNode {
+            kind: NodeKind::Doctype,
+            ..HtmlNode::leaf_node(None, name, None)
+        }
+    }
+
+    pub fn fragment_node(children: Vec<HtmlNode>) -> Self {
+        HtmlNode {
+            tag: None,
+            value: None,
+            children: children.into_iter().map(Box::new).collect(),
+            props: None,
+            kind: NodeKind::Fragment,
+        }
+    }
+
+    /// A node holding trusted HTML that `to_html` writes without escaping.
+    pub fn raw_node(html: &str) -> Self {
+        HtmlNode {
+            kind: NodeKind::Raw,
+            ..HtmlNode::leaf_node(None, html, None)
         }
     }
 
     pub fn to_html(&self) -> Result<String, NodeError> {
+        self.to_html_with_options(&HtmlOptions::default())
+    }
+
+    pub fn to_html_with_options(&self, options: &HtmlOptions) -> Result<String, NodeError> {
         let mut finalhtml = String::new();
-        // base case for leaf nodes
-        if self.children.is_empty() {
-            if self.value.is_none() {
-                return Err(NodeError::ValueError(finalhtml));
-            } else {
-                let text = self.value.clone().unwrap();
-                let props = self.props_to_html();
-                match &self.tag {
-                    Some(tag) => {
-                        return Ok(format!("<{}{}>{}</{}>", tag, props, text, tag));
-                    },
-                    None => {
-                        return Ok(format!("{}", text));
+        self.write_html(&mut finalhtml, options)?;
+        Ok(finalhtml)
+    }
+
+    /// Serializes straight into `out` without building intermediate strings.
+    /// On error, whatever was written before the offending node stays in `out`.
+    pub fn write_html<W: fmt::Write>(&self, out: &mut W, options: &HtmlOptions) -> Result<(), NodeError> {
+        self.write_html_in(out, options, Context::default())
+    }
+
+    /// Like `write_html`, for byte sinks such as a `BufWriter<File>`.
+    pub fn write_html_io<W: io::Write>(&self, out: &mut W, options: &HtmlOptions) -> Result<(), NodeError> {
+        let mut adapter = IoAdapter { inner: out, error: None };
+        self.write_html(&mut adapter, options)
+            .map_err(|e| adapter.error.take().map_or(e, NodeError::IoError))
+    }
+
+    fn is_block(&self) -> bool {
+        matches!(self.kind, NodeKind::Comment | NodeKind::Doctype)
+            || self.tag.as_deref().is_some_and(is_block_element)
+    }
+
+    /// Text nodes holding nothing but whitespace, like the indentation
+    /// between tags in a parsed template.
+    fn is_blank_text(&self) -> bool {
+        self.kind == NodeKind::Text
+            && self.tag.is_none()
+            && self.value.as_deref().is_some_and(|v| v.trim().is_empty())
+    }
+
+    fn write_open_tag<W: fmt::Write>(&self, out: &mut W, tag: &str, options: &HtmlOptions) -> fmt::Result {
+        write!(out, "<{tag}")?;
+        self.write_props_with(out, options.format == OutputFormat::Minified)?;
+        out.write_str(">")
+    }
+
+    fn write_html_in<W: fmt::Write>(&self, out: &mut W, options: &HtmlOptions, ctx: Context) -> Result<(), NodeError> {
+        let ctx = match self.tag.as_deref() {
+            Some(tag) => Context {
+                raw_text: ctx.raw_text || RAW_TEXT_ELEMENTS.contains(&tag),
+                preformatted: ctx.preformatted || PREFORMATTED_ELEMENTS.contains(&tag),
+                depth: ctx.depth,
+            },
+            None => ctx,
+        };
+        match self.kind {
+            NodeKind::Comment => {
+                if options.format != OutputFormat::Minified {
+                    let text = self.value.as_deref().unwrap_or_default();
+                    write!(out, "<!--{}-->", text.replace("-->", "-- >"))?;
+                }
+                return Ok(());
+            },
+            NodeKind::Doctype => {
+                write!(out, "<!doctype {}>", self.value.as_deref().unwrap_or("html"))?;
+                return Ok(());
+            },
+            NodeKind::Fragment => {
+                let pretty = options.format == OutputFormat::Pretty && !ctx.preformatted;
+                let mut first = true;
+                for child in &self.children {
+                    if pretty && child.is_blank_text() {
+                        continue;
+                    }
+                    if pretty && !first && child.is_block() {
+                        write!(out, "\n{}", "  ".repeat(ctx.depth))?;
                     }
+                    child.write_html_in(out, options, ctx)?;
+                    first = false;
                 }
+                return Ok(());
+            },
+            NodeKind::Text | NodeKind::Raw => {},
+        }
+        if let Some(tag) = self.tag.as_deref().filter(|t| is_void_element(t)) {
+            if !self.children.is_empty() || self.value.as_deref().is_some_and(|v| !v.is_empty()) {
+                return Err(NodeError::ValueError(format!("<{tag}> cannot have content")));
+            }
+            write!(out, "<{tag}")?;
+            self.write_props_with(out, options.format == OutputFormat::Minified)?;
+            out.write_str(if options.xhtml { " />" } else { ">" })?;
+            return Ok(());
+        }
+        // base case for leaf nodes
+        if self.children.is_empty() {
+            let Some(text) = &self.value else {
+                return Err(NodeError::ValueError(format!("leaf node {:?} has no value", self.tag)));
+            };
+            if let Some(tag) = &self.tag {
+                self.write_open_tag(out, tag, options)?;
             }
+            match self.kind {
+                NodeKind::Raw => out.write_str(text)?,
+                NodeKind::Text if ctx.raw_text => out.write_str(text)?,
+                NodeKind::Text if options.format == OutputFormat::Minified && !ctx.preformatted => {
+                    write_collapsed(out, text)?
+                },
+                _ => write_escaped(out, text, false)?,
+            }
+            if let Some(tag) = &self.tag {
+                write!(out, "</{tag}>")?;
+            }
+            return Ok(());
         }
 
         // now work on parent nodes
-        if self.tag.is_none() {
-            return Err(NodeError::ValueError(finalhtml));
+        let Some(tag) = &self.tag else {
+            return Err(NodeError::ValueError("parent node has no tag".to_string()));
+        };
+        // pretty printing only breaks lines around block children, so inline
+        // markup keeps its exact spacing
+        let block_layout = options.format == OutputFormat::Pretty
+            && !ctx.preformatted
+            && self.children.iter().any(|c| c.is_block());
+        let child_ctx = Context { depth: ctx.depth + 1, ..ctx };
+        self.write_open_tag(out, tag, options)?;
+        for child in &self.children {
+            if block_layout && child.is_blank_text() {
+                continue;
+            }
+            if block_layout {
+                write!(out, "\n{}", "  ".repeat(child_ctx.depth))?;
+            }
+            child.write_html_in(out, options, child_ctx)?;
+        }
+        if block_layout {
+            write!(out, "\n{}", "  ".repeat(ctx.depth))?;
+        }
+        write!(out, "</{tag}>")?;
+        Ok(())
+    }
+
+    pub fn get_attr(&self, key: &str) -> Option<&str> {
+        self.props.as_ref().and_then(|props| props.get(key))
+    }
+
+    pub fn set_attr(&mut self, key: &str, value: &str) {
+        self.props.get_or_insert_with(Attributes::new).insert(key, value);
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.get_attr("class")
+            .is_some_and(|classes| classes.split_whitespace().any(|c| c == class))
+    }
+
+    /// Appends `class` to the `class` attribute unless it's already there.
+    pub fn add_class(&mut self, class: &str) {
+        if self.has_class(class) {
+            return;
+        }
+        let classes = match self.get_attr("class") {
+            Some(existing) if !existing.trim().is_empty() => format!("{existing} {class}"),
+            _ => class.to_string(),
+        };
+        self.set_attr("class", &classes);
+    }
+
+    /// Concatenated text of this node and all its descendants, ignoring tags.
+    pub fn text_content(&self) -> String {
+        if matches!(self.kind, NodeKind::Comment | NodeKind::Doctype) {
+            return String::new();
         }
-        let open_tag = format!("<{}>", &self.tag.clone().unwrap());
-        let close_tag = format!("</{}>", &self.tag.clone().unwrap());
-        finalhtml.push_str(&open_tag);
+        let mut text = self.value.clone().unwrap_or_default();
         for child in &self.children {
-            let sub_html = child.to_html()?;
-            finalhtml.push_str(&sub_html);
+            text.push_str(&child.text_content());
         }
-        finalhtml.push_str(&close_tag);
-        Ok(finalhtml)
+        text
     }
 
     pub fn props_to_html(&self) -> String {
         let mut result = String::new();
+        let _ = self.write_props_with(&mut result, false);
+        result
+    }
+
+    fn write_props_with<W: fmt::Write>(&self, out: &mut W, unquoted: bool) -> fmt::Result {
         if let Some(props) = &self.props {
-            for (key, value) in props {
-                result.push_str(format!(r#" {}="{}""#, key, value).as_str());
+            for (key, value) in props.iter() {
+                write!(out, " {key}")?;
+                match value {
+                    Some(value) if unquoted && is_unquotable(value) => write!(out, "={value}")?,
+                    Some(value) => {
+                        out.write_str("=\"")?;
+                        write_escaped(out, value, true)?;
+                        out.write_str("\"")?;
+                    },
+                    None => {},
+                }
             }
         }
+        Ok(())
+    }
+}
 
-        result
+/// Formats the node as HTML with default options. Formatting an invalid
+/// node (see `to_html`) fails, so prefer `to_html` when the tree isn't trusted.
+impl fmt::Display for HtmlNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_html(f, &HtmlOptions::default()).map_err(|_| fmt::Error)
     }
-}
\ No newline at end of file
+}
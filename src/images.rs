@@ -0,0 +1,184 @@
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use crate::htmlnode::HtmlNode;
+use crate::sanitize::url_scheme;
+
+/// Where `add_image_dimensions` looks for image files and what it adds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageOptions {
+    /// Directories published at the site root, searched in order. A URL of
+    /// `/images/a.png` is looked for as `<root>/images/a.png`.
+    pub roots: Vec<PathBuf>,
+    /// Add `loading="lazy"` and `decoding="async"` to every image.
+    pub lazy: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            roots: vec![PathBuf::from("static"), PathBuf::from("content")],
+            lazy: true,
+        }
+    }
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn be_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_u24(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 3)?;
+    Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+}
+
+/// The width and height of a PNG, GIF, JPEG, WebP or BMP image, read from
+/// its header. `None` if the format isn't recognised.
+pub fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") && bytes.get(12..16) == Some(b"IHDR") {
+        Some((be_u32(bytes, 16)?, be_u32(bytes, 20)?))
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some((le_u16(bytes, 6)?, le_u16(bytes, 8)?))
+    } else if bytes.starts_with(&[0xFF, 0xD8]) {
+        jpeg_dimensions(bytes)
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        webp_dimensions(bytes)
+    } else if bytes.starts_with(b"BM") {
+        let width = i32::from_le_bytes(bytes.get(18..22)?.try_into().ok()?);
+        let height = i32::from_le_bytes(bytes.get(22..26)?.try_into().ok()?);
+        // a negative height means the rows are stored top-down
+        Some((width.unsigned_abs(), height.unsigned_abs()))
+    } else {
+        None
+    }
+}
+
+/// Reads only as much of an image file as `image_dimensions` needs: the first
+/// 64 KiB, and more for a JPEG whose frame header comes after that.
+fn file_dimensions(file: &Path) -> std::io::Result<Option<(u32, u32)>> {
+    let mut reader = std::fs::File::open(file)?;
+    let mut bytes = Vec::new();
+    let mut limit = 64 * 1024;
+    loop {
+        let wanted = limit - bytes.len();
+        let read = (&mut reader).take(wanted as u64).read_to_end(&mut bytes)?;
+        let dimensions = image_dimensions(&bytes);
+        if dimensions.is_some() || read < wanted || !bytes.starts_with(&[0xFF, 0xD8]) {
+            return Ok(dimensions);
+        }
+        limit *= 4;
+    }
+}
+
+/// Walks the JPEG segments up to the first start-of-frame marker.
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let mut at = 2;
+    loop {
+        while *bytes.get(at)? != 0xFF {
+            at += 1;
+        }
+        while *bytes.get(at)? == 0xFF {
+            at += 1;
+        }
+        let marker = *bytes.get(at)?;
+        at += 1;
+        match marker {
+            // standalone markers without a length
+            0x01 | 0xD0..=0xD9 => continue,
+            // SOF0-SOF15, apart from DHT (C4), JPG (C8) and DAC (CC)
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                return Some((be_u16(bytes, at + 5)?, be_u16(bytes, at + 3)?));
+            },
+            _ => at += be_u16(bytes, at)? as usize,
+        }
+    }
+}
+
+fn webp_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    match bytes.get(12..16)? {
+        b"VP8 " => Some((le_u16(bytes, 26)? & 0x3FFF, le_u16(bytes, 28)? & 0x3FFF)),
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        },
+        b"VP8X" => Some((le_u24(bytes, 24)? + 1, le_u24(bytes, 27)? + 1)),
+        _ => None,
+    }
+}
+
+/// The path of a local URL relative to the site root, for a page published
+/// in `page_dir` (also relative to the site root). `None` for URLs on other
+/// sites, `data:` URLs and paths whose `..` climbs above the site root.
+pub fn site_path(url: &str, page_dir: &Path) -> Option<PathBuf> {
+    if url_scheme(url).is_some() || url.starts_with("//") {
+        return None;
+    }
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    if path.is_empty() {
+        return None;
+    }
+    let joined = match path.strip_prefix('/') {
+        Some(absolute) => PathBuf::from(absolute),
+        None => page_dir.join(path),
+    };
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            // pops a directory, or fails when there is none left to pop
+            Component::ParentDir if !resolved.pop() => return None,
+            _ => {},
+        }
+    }
+    Some(resolved)
+}
+
+/// The file a local URL is served from, if it exists under one of `roots`.
+pub fn find_asset(url: &str, page_dir: &Path, roots: &[PathBuf]) -> Option<PathBuf> {
+    let path = site_path(url, page_dir)?;
+    roots.iter().map(|root| root.join(&path)).find(|file| file.is_file())
+}
+
+/// Adds `width` and `height` from the image file to every local `img` in the
+/// tree, plus lazy loading hints when enabled. Returns the URLs of images that
+/// couldn't be found.
+pub fn add_image_dimensions(node: &mut HtmlNode, page_dir: &Path, options: &ImageOptions) -> Vec<String> {
+    let mut missing = Vec::new();
+    node.walk_mut(|node| {
+        if node.tag.as_deref() != Some("img") {
+            return;
+        }
+        if options.lazy {
+            for (key, value) in [("loading", "lazy"), ("decoding", "async")] {
+                if node.get_attr(key).is_none() {
+                    node.set_attr(key, value);
+                }
+            }
+        }
+        let Some(src) = node.get_attr("src").map(str::to_string) else {
+            return;
+        };
+        if site_path(&src, page_dir).is_none() || node.get_attr("width").is_some() || node.get_attr("height").is_some() {
+            return;
+        }
+        let dimensions = find_asset(&src, page_dir, &options.roots)
+            .and_then(|file| file_dimensions(&file).ok());
+        match dimensions {
+            Some(Some((width, height))) => {
+                node.set_attr("width", &width.to_string());
+                node.set_attr("height", &height.to_string());
+            },
+            Some(None) => {},
+            None => missing.push(src),
+        }
+    });
+    missing
+}
@@ -2,5 +2,24 @@ pub mod errors;
 pub mod utils;
 pub mod textnode;
 pub mod htmlnode;
+pub mod attributes;
 pub mod blocks;
-pub mod tests;
\ No newline at end of file
+pub mod slug;
+pub mod toc;
+pub mod visit;
+pub mod select;
+pub mod parse;
+pub mod builder;
+pub mod sanitize;
+pub mod links;
+pub mod images;
+pub mod responsive;
+pub mod highlight;
+pub mod math;
+pub mod url;
+pub mod template;
+pub mod check;
+
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests;
@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use crate::errors::NodeError;
+use crate::htmlnode::HtmlNode;
+use crate::images::site_path;
+use crate::sanitize::url_scheme;
+
+/// How links to other sites are decorated.
+///
+/// A link is external when it is an absolute `http`/`https` (or
+/// protocol-relative `//`) URL outside `site_url`. Relative links, fragments
+/// and other schemes such as `mailto:` are internal and left untouched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkPolicy {
+    /// Where the site is published, e.g. `https://example.com/blog/`.
+    /// Absolute links under it count as internal.
+    pub site_url: Option<String>,
+    /// Tokens added to `rel` on external links.
+    pub rel: Vec<String>,
+    /// Open external links in a new tab with `target="_blank"`.
+    pub new_tab: bool,
+    /// Class added to external links, e.g. `external`.
+    pub external_class: Option<String>,
+}
+
+impl Default for LinkPolicy {
+    fn default() -> Self {
+        LinkPolicy {
+            site_url: None,
+            rel: vec!["noopener".to_string(), "noreferrer".to_string()],
+            new_tab: false,
+            external_class: None,
+        }
+    }
+}
+
+/// `url` without its scheme, query or fragment and with the host in lower
+/// case, e.g. `//example.com/blog`.
+fn without_scheme(url: &str) -> Option<String> {
+    let rest = match url_scheme(url).as_deref() {
+        Some("http" | "https") => url.trim_start().split_once(':')?.1,
+        Some(_) => return None,
+        None => url.trim_start(),
+    };
+    let rest = rest.strip_prefix("//")?;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    Some(format!("//{}{path}", host.to_ascii_lowercase()))
+}
+
+impl LinkPolicy {
+    pub fn is_external(&self, url: &str) -> bool {
+        let Some(target) = without_scheme(url) else {
+            return false;
+        };
+        match self.site_url.as_deref().and_then(without_scheme) {
+            Some(site) => {
+                let site = site.trim_end_matches('/');
+                !(target == site || target.starts_with(&format!("{site}/")))
+            },
+            None => true,
+        }
+    }
+
+    /// Decorates every external `a` element in the tree.
+    pub fn apply(&self, node: &mut HtmlNode) {
+        node.walk_mut(|node| {
+            let external = node.tag.as_deref() == Some("a")
+                && node.get_attr("href").is_some_and(|href| self.is_external(href));
+            if external {
+                self.decorate(node);
+            }
+        });
+    }
+
+    fn decorate(&self, node: &mut HtmlNode) {
+        if !self.rel.is_empty() {
+            let mut rel: Vec<String> = node.get_attr("rel")
+                .map(|rel| rel.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default();
+            for token in &self.rel {
+                if !rel.iter().any(|existing| existing.eq_ignore_ascii_case(token)) {
+                    rel.push(token.clone());
+                }
+            }
+            node.set_attr("rel", &rel.join(" "));
+        }
+        if self.new_tab {
+            node.set_attr("target", "_blank");
+        }
+        if let Some(class) = &self.external_class {
+            node.add_class(class);
+        }
+    }
+}
+
+/// The root-relative URL of the page generated from `source`, a Markdown
+/// file relative to the content directory: `blog/tom/index.md` is served as
+/// `/blog/tom/` and `about.md` as `/about.html`.
+pub fn page_url(source: &Path) -> String {
+    let mut parts: Vec<String> = source.iter().map(|part| part.to_string_lossy().into_owned()).collect();
+    match parts.pop() {
+        Some(file) if file != "index.md" => {
+            let stem = Path::new(&file).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or(file);
+            parts.push(format!("{stem}.html"));
+        },
+        _ => parts.push(String::new()),
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Points links at Markdown sources, such as `../tom/index.md` or
+/// `/blog/tom/index.md`, to the pages generated from them, keeping any
+/// `?query` or `#fragment`. `page_dir` is the linking page's directory
+/// within `content_dir`. A link to a source that doesn't exist is an error.
+pub fn resolve_page_links(node: &mut HtmlNode, page_dir: &Path, content_dir: &Path) -> Result<(), NodeError> {
+    let mut result = Ok(());
+    node.walk_mut(|node| {
+        if node.tag.as_deref() != Some("a") || result.is_err() {
+            return;
+        }
+        let Some(href) = node.get_attr("href") else {
+            return;
+        };
+        let path = href.split(['?', '#']).next().unwrap_or_default();
+        let is_external = url_scheme(href).is_some() || href.starts_with("//");
+        if is_external || Path::new(path).extension().is_none_or(|ext| ext != "md") {
+            return;
+        }
+        // a `..` above the content root is as missing as a file that isn't there
+        let Some(source) = site_path(href, page_dir).filter(|source| content_dir.join(source).is_file()) else {
+            result = Err(NodeError::ValueError(format!("link to missing page {href:?}")));
+            return;
+        };
+        let suffix = href.find(['?', '#']).map_or("", |i| &href[i..]);
+        let url = format!("{}{suffix}", page_url(&source));
+        node.set_attr("href", &url);
+    });
+    result
+}
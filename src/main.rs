@@ -1,6 +1,62 @@
-use std::{io::{Read, Write}, path::Path};
+use std::{io::{BufWriter, Read, Write}, path::Path};
 
-use rust_static_site::{blocks::{extract_heading_content, get_heading_block_tag, markdown_to_blocks, markdown_to_html_node}, errors::NodeError};
+use rust_static_site::{blocks::{extract_heading_content, get_heading_block_tag, markdown_to_blocks, markdown_to_document, Document, MarkdownOptions}, check::check_site, errors::NodeError, highlight::{HighlightMode, HighlightOptions}, htmlnode::{HtmlNode, HtmlOptions, OutputFormat}, images::{add_image_dimensions, ImageOptions}, links::resolve_page_links, parse::parse_html, responsive::{add_responsive_images, ResponsiveOptions}, sanitize::SanitizePolicy, template::fill_template, url::{rebase_urls, BasePath}};
+
+/// Markdown pages, plus images and other files that sit next to them.
+const CONTENT_DIR: &str = "content";
+/// Files copied to the site root as they are.
+const STATIC_DIR: &str = "static";
+/// Where the site is written.
+const DEST_DIR: &str = "docs";
+
+/// Settings taken from the command line.
+struct Config {
+    /// URL prefix the site is served under, e.g. `/rust-static-site/`.
+    base_path: BasePath,
+    html: HtmlOptions,
+    markdown: MarkdownOptions,
+    images: ImageOptions,
+    /// Resized copies of images, unless disabled with `--no-responsive`.
+    responsive: Option<ResponsiveOptions>,
+    /// Also write each page's node tree as JSON next to its HTML.
+    json: bool,
+}
+
+/// Parses `[--pretty | --minify] [--sanitize] [--site-url=URL] [--new-tab] [--no-responsive]
+/// [--highlight=classes|inline|off] [--json] [BASE_PATH]`.
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Config, String> {
+    let mut base_path = None;
+    let mut html = HtmlOptions::default();
+    let mut markdown = MarkdownOptions::default();
+    let mut json = false;
+    let mut responsive = Some(ResponsiveOptions { output_dir: DEST_DIR.into(), ..Default::default() });
+    for arg in args {
+        match arg.as_str() {
+            "--pretty" => html.format = OutputFormat::Pretty,
+            "--minify" => html.format = OutputFormat::Minified,
+            "--sanitize" => markdown.sanitize = SanitizePolicy::strict(),
+            "--new-tab" => markdown.links.new_tab = true,
+            "--no-responsive" => responsive = None,
+            "--highlight=classes" => markdown.highlight = Some(HighlightOptions::default()),
+            "--highlight=inline" => {
+                markdown.highlight = Some(HighlightOptions { mode: HighlightMode::InlineStyles, ..Default::default() });
+            },
+            "--highlight=off" => markdown.highlight = None,
+            flag if flag.starts_with("--site-url=") => {
+                markdown.links.site_url = flag.strip_prefix("--site-url=").map(str::to_string);
+            },
+            "--json" if cfg!(feature = "serde") => json = true,
+            "--json" => return Err("--json requires building with the `serde` feature".to_string()),
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {flag}")),
+            _ if base_path.is_none() => {
+                base_path = Some(arg.parse().map_err(|e| format!("Invalid base path: {e:?}"))?);
+            },
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+    let images = ImageOptions { roots: vec![STATIC_DIR.into(), CONTENT_DIR.into()], ..Default::default() };
+    Ok(Config { base_path: base_path.unwrap_or_default(), html, markdown, images, responsive, json })
+}
 
 fn clean_and_copy(origin: &Path, dest: &Path) -> Result<(), std::io::Error> {
     if dest.exists() {
@@ -39,7 +95,7 @@ fn extract_title(markdown: &str) -> Result<String, NodeError> {
     }   
 }
 
-fn generate_page(from_path: &Path, template_path: &Path, dest_path: &Path, base_path: &Path) -> Result<(), std::io::Error> {
+fn generate_page(from_path: &Path, template_path: &Path, dest_path: &Path, config: &Config) -> Result<(), std::io::Error> {
     println!("Generating page from {:?} -> {:?} using {:?}", from_path, dest_path, template_path);
     let mut source_file = std::fs::File::open(from_path)?;
     let mut source_text = String::new();
@@ -50,19 +106,44 @@ fn generate_page(from_path: &Path, template_path: &Path, dest_path: &Path, base_
     template_file.read_to_string(&mut template_text)?;
 
 
-    let source_html = markdown_to_html_node(&source_text)
-        .to_html()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("HTML Conversion failed: {:?}", e)))?;
-    
-    let page_title = extract_title(&markdown_to_blocks(&source_text).first().unwrap_or(&"".to_string()))
+    let options = &config.markdown;
+    let mut document = markdown_to_document(&source_text, options);
+    let page_dir = from_path.parent()
+        .and_then(|dir| dir.strip_prefix(CONTENT_DIR).ok())
+        .unwrap_or(Path::new(""));
+    resolve_page_links(&mut document.content, page_dir, Path::new(CONTENT_DIR))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::NotFound, format!("Broken link in {from_path:?}: {e:?}")))?;
+    for url in &document.removed_urls {
+        println!("Warning: removed unsafe URL {url:?} from {from_path:?}");
+    }
+    for src in add_image_dimensions(&mut document.content, page_dir, &config.images) {
+        println!("Warning: image {src:?} in {from_path:?} doesn't exist");
+    }
+    if let Some(responsive) = &config.responsive {
+        let failed = add_responsive_images(&mut document.content, page_dir, &config.images, responsive)
+            .map_err(|e| std::io::Error::other(format!("Image processing failed: {:?}", e)))?;
+        for src in failed {
+            println!("Warning: image {src:?} in {from_path:?} couldn't be decoded");
+        }
+    }
+    let toc = document.toc()
+        .unwrap_or_else(|| HtmlNode::text_node(""));
+
+    let page_title = extract_title(markdown_to_blocks(&source_text).first().unwrap_or(&"".to_string()))
         .unwrap_or_default();
     println!("DEBUG: TITLE = {page_title}");
 
-    let page_html = template_text
-        .replace("{{ Title }}", &page_title)
-        .replace("{{ Content }}", &source_html)
-        .replace(r#"href="/"#, &format!(r#"href="{}"#, base_path.display()))
-        .replace(r#"src="/"#, &format!(r#"src="{}"#, base_path.display()));
+    if config.json {
+        write_json(&document, &dest_path.with_extension("json"))?;
+    }
+
+    let mut page = parse_html(&template_text);
+    fill_template(&mut page, vec![
+        ("{{ Title }}", HtmlNode::text_node(&page_title)),
+        ("{{ Toc }}", toc),
+        ("{{ Content }}", document.content),
+    ]);
+    rebase_urls(&mut page, &config.base_path);
 
     if dest_path.is_file() {
         println!("{:?} file already exists.", dest_path);
@@ -70,12 +151,31 @@ fn generate_page(from_path: &Path, template_path: &Path, dest_path: &Path, base_
         println!("{:?} doesn't exist, creating it now...", dest_path);
     }
 
-    let mut dest_file = std::fs::File::create(dest_path)?;
-    dest_file.write_all(page_html.as_bytes())?;
+    let mut dest_file = BufWriter::new(std::fs::File::create(dest_path)?);
+    page.write_html_io(&mut dest_file, &config.html)
+        .map_err(|e| match e {
+            NodeError::IoError(e) => e,
+            e => std::io::Error::other(format!("HTML Conversion failed: {:?}", e)),
+        })?;
+    dest_file.flush()?;
     Ok(())
 }
 
-fn generate_page_recursive(dir_path_content: &Path, template_path: &Path, dest_dir_path: &Path, base_path: &Path) -> Result<(), std::io::Error> {
+/// Writes the page's node tree and headings for consumers that don't want HTML.
+#[cfg(feature = "serde")]
+fn write_json(document: &Document, dest_path: &Path) -> Result<(), std::io::Error> {
+    println!("Writing node tree: {:?}", dest_path);
+    let mut json_file = BufWriter::new(std::fs::File::create(dest_path)?);
+    serde_json::to_writer_pretty(&mut json_file, document)?;
+    json_file.flush()
+}
+
+#[cfg(not(feature = "serde"))]
+fn write_json(_document: &Document, _dest_path: &Path) -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "--json requires the serde feature"))
+}
+
+fn generate_page_recursive(dir_path_content: &Path, template_path: &Path, dest_dir_path: &Path, config: &Config) -> Result<(), std::io::Error> {
     println!("Recursively generating website...");
     for entry in std::fs::read_dir(dir_path_content)? {
         let entry = entry?;
@@ -88,8 +188,8 @@ fn generate_page_recursive(dir_path_content: &Path, template_path: &Path, dest_d
             println!("Making Dest Path: {:?}", &new_dest_path);
             std::fs::create_dir(&new_dest_path)?;
             println!("Does it now exists? {}", &new_dest_path.exists());
-            generate_page_recursive(&child_path, template_path, &new_dest_path, base_path)?;
-        } else if child_path.is_file() && child_path.extension().map_or(false, |ext| ext == "md") {
+            generate_page_recursive(&child_path, template_path, &new_dest_path, config)?;
+        } else if child_path.is_file() && child_path.extension().is_some_and(|ext| ext == "md") {
             println!("Found markdown file: {:?}", child_path);
             let parent_path = new_dest_path.parent()
                 .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "No parent directory"))?;
@@ -101,25 +201,48 @@ fn generate_page_recursive(dir_path_content: &Path, template_path: &Path, dest_d
                 )
             ));
             println!("Generating new file: {:?}", dest_filepath);
-            generate_page(&child_path, template_path, &dest_filepath, base_path)?;
+            generate_page(&child_path, template_path, &dest_filepath, config)?;
+        } else if child_path.is_file() {
+            println!("Copying asset: {:?} -> {:?}", child_path, new_dest_path);
+            std::fs::copy(&child_path, &new_dest_path)?;
         }
     }
 
     Ok(())
 }
 
+/// Checks the site last generated in `DEST_DIR`, for `check [BASE_PATH]`.
+fn check(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let problems = check_site(Path::new(DEST_DIR), Path::new(CONTENT_DIR), &config.base_path)
+        .map_err(|e| format!("Checking {DEST_DIR} failed: {e:?}"))?;
+    for problem in &problems {
+        println!("{problem}");
+    }
+    match problems.len() {
+        0 => Ok(()),
+        n => Err(format!("{n} problems found in {DEST_DIR}").into()),
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let source = Path::new("content");
-    let dest = Path::new("docs");
-    let basepathstr = std::env::args().skip(1).next().unwrap_or("/".to_string());
-    let basepath = Path::new(&basepathstr);
+    let source = Path::new(CONTENT_DIR);
+    let dest = Path::new(DEST_DIR);
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if_eq("check").is_some() {
+        let config = parse_args(args)?;
+        return check(&config);
+    }
+    let config = parse_args(args)?;
 
-    clean_and_copy(Path::new("static"), dest)?;
+    clean_and_copy(Path::new(STATIC_DIR), dest)?;
+    // linked from the template, so written even when highlighting is off
+    let theme = config.markdown.highlight.clone().unwrap_or_default().theme;
+    std::fs::write(dest.join("highlight.css"), theme.css())?;
     generate_page_recursive(
         source, 
         Path::new("template.html"), 
         dest,
-        basepath,
+        &config,
     )?;
 
     Ok(())
@@ -129,6 +252,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 mod maintests {
     use super::*;
 
+    #[test]
+    fn args_parsing() {
+        let args = ["--minify", "--sanitize", "--site-url=https://example.com/", "/rust-static-site/"].map(String::from);
+        let config = parse_args(args.into_iter()).unwrap();
+        assert_eq!("/rust-static-site/", config.base_path.as_str());
+        assert_eq!(OutputFormat::Minified, config.html.format);
+        assert_eq!(SanitizePolicy::strict(), config.markdown.sanitize);
+        assert_eq!(Some("https://example.com/"), config.markdown.links.site_url.as_deref());
+        assert!(parse_args(["--shiny".to_string()].into_iter()).is_err());
+        assert_eq!(cfg!(feature = "serde"), parse_args(["--json".to_string()].into_iter()).is_ok());
+    }
+
     #[test]
     fn title_extraction() {
         let markdown = "# Hello";
@@ -0,0 +1,386 @@
+use crate::htmlnode::HtmlNode;
+
+/// Greek letters, written as identifiers.
+const GREEK: &[(&str, &str)] = &[
+    ("alpha", "α"), ("beta", "β"), ("gamma", "γ"), ("delta", "δ"), ("epsilon", "ϵ"), ("varepsilon", "ε"),
+    ("zeta", "ζ"), ("eta", "η"), ("theta", "θ"), ("vartheta", "ϑ"), ("iota", "ι"), ("kappa", "κ"),
+    ("lambda", "λ"), ("mu", "μ"), ("nu", "ν"), ("xi", "ξ"), ("pi", "π"), ("rho", "ρ"), ("sigma", "σ"),
+    ("tau", "τ"), ("upsilon", "υ"), ("phi", "ϕ"), ("varphi", "φ"), ("chi", "χ"), ("psi", "ψ"),
+    ("omega", "ω"), ("Gamma", "Γ"), ("Delta", "Δ"), ("Theta", "Θ"), ("Lambda", "Λ"), ("Xi", "Ξ"),
+    ("Pi", "Π"), ("Sigma", "Σ"), ("Upsilon", "Υ"), ("Phi", "Φ"), ("Psi", "Ψ"), ("Omega", "Ω"),
+    ("infty", "∞"), ("partial", "∂"), ("nabla", "∇"), ("ell", "ℓ"), ("hbar", "ℏ"), ("emptyset", "∅"),
+];
+
+/// Operators, relations, arrows and delimiters.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("times", "×"), ("cdot", "⋅"), ("pm", "±"), ("mp", "∓"), ("div", "÷"), ("ast", "∗"), ("circ", "∘"),
+    ("leq", "≤"), ("le", "≤"), ("geq", "≥"), ("ge", "≥"), ("neq", "≠"), ("ne", "≠"), ("approx", "≈"),
+    ("equiv", "≡"), ("sim", "∼"), ("simeq", "≃"), ("cong", "≅"), ("propto", "∝"), ("ll", "≪"), ("gg", "≫"),
+    ("to", "→"), ("rightarrow", "→"), ("leftarrow", "←"), ("gets", "←"), ("leftrightarrow", "↔"),
+    ("Rightarrow", "⇒"), ("Leftarrow", "⇐"), ("Leftrightarrow", "⇔"), ("implies", "⟹"), ("iff", "⟺"),
+    ("mapsto", "↦"), ("in", "∈"), ("notin", "∉"), ("ni", "∋"), ("subset", "⊂"), ("subseteq", "⊆"),
+    ("supset", "⊃"), ("supseteq", "⊇"), ("cup", "∪"), ("cap", "∩"), ("setminus", "∖"), ("forall", "∀"),
+    ("exists", "∃"), ("neg", "¬"), ("land", "∧"), ("wedge", "∧"), ("lor", "∨"), ("vee", "∨"),
+    ("oplus", "⊕"), ("otimes", "⊗"), ("mid", "∣"), ("parallel", "∥"), ("perp", "⊥"), ("angle", "∠"),
+    ("ldots", "…"), ("cdots", "⋯"), ("vdots", "⋮"), ("ddots", "⋱"), ("dots", "…"), ("prime", "′"),
+    ("langle", "⟨"), ("rangle", "⟩"), ("lfloor", "⌊"), ("rfloor", "⌋"), ("lceil", "⌈"), ("rceil", "⌉"),
+    ("lbrace", "{"), ("rbrace", "}"), ("vert", "|"), ("Vert", "‖"), ("mod", "mod"), ("bmod", "mod"),
+];
+
+/// Operators whose limits go above and below them in display math.
+const LARGE_OPERATORS: &[(&str, &str)] = &[
+    ("sum", "∑"), ("prod", "∏"), ("coprod", "∐"), ("bigcup", "⋃"), ("bigcap", "⋂"), ("bigoplus", "⨁"),
+    ("lim", "lim"), ("limsup", "lim sup"), ("liminf", "lim inf"), ("max", "max"), ("min", "min"),
+    ("sup", "sup"), ("inf", "inf"),
+];
+
+/// Integrals keep their limits to the side.
+const INTEGRALS: &[(&str, &str)] = &[("int", "∫"), ("iint", "∬"), ("iiint", "∭"), ("oint", "∮")];
+
+/// Named functions, written upright.
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "log", "ln", "lg", "exp", "det", "gcd", "arg", "dim", "deg", "ker", "Pr",
+];
+
+/// Accents placed over (or under) their argument.
+const ACCENTS: &[(&str, &str)] = &[
+    ("hat", "^"), ("widehat", "^"), ("bar", "¯"), ("overline", "¯"), ("vec", "→"), ("dot", "˙"),
+    ("ddot", "¨"), ("tilde", "~"), ("widetilde", "~"), ("overrightarrow", "→"),
+];
+
+/// Widths of the spacing commands, by the character or name after `\`.
+const SPACES: &[(&str, &str)] = &[
+    (",", "0.1667em"), (":", "0.2222em"), (">", "0.2222em"), (";", "0.2778em"), (" ", "0.25em"),
+    ("!", "-0.1667em"), ("quad", "1em"), ("qquad", "2em"),
+];
+
+/// How deeply groups and commands may nest before the rest of the formula
+/// is given up on, so hostile input can't exhaust the stack.
+const MAX_DEPTH: usize = 256;
+
+fn lookup(table: &[(&str, &'static str)], name: &str) -> Option<&'static str> {
+    table.iter().find(|(key, _)| *key == name).map(|(_, value)| *value)
+}
+
+/// Converts a TeX formula such as `\frac{a^2}{2}` into a `math` element.
+///
+/// Covers the everyday subset: sub- and superscripts, fractions, roots,
+/// Greek letters, common operators and relations, `\left`/`\right`,
+/// accents, `\text` and spacing. Anything else shows up as an `merror` with
+/// the unknown command, rather than failing the page.
+pub fn latex_to_mathml(tex: &str, display: bool) -> HtmlNode {
+    let mut parser = Parser { rest: tex, display, depth: 0 };
+    let row = parser.row(End::Input);
+    HtmlNode::el("math")
+        .attr("display", if display { "block" } else { "inline" })
+        .child(mrow(row))
+}
+
+fn token(tag: &str, text: &str) -> HtmlNode {
+    HtmlNode::leaf_node(Some(tag), text, None)
+}
+
+/// Groups `nodes` into one node, without a needless `mrow` around a single one.
+fn mrow(mut nodes: Vec<HtmlNode>) -> HtmlNode {
+    if nodes.len() == 1 {
+        nodes.remove(0)
+    } else {
+        HtmlNode::el("mrow").children(nodes)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum End {
+    Input,
+    /// A closing `}`.
+    Brace,
+    /// `\right`.
+    Right,
+    /// A closing `]`, for the index of `\sqrt[n]`.
+    Bracket,
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+    display: bool,
+    /// Rows and atoms currently being parsed.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        Some(c)
+    }
+
+    /// Reads a command name after its `\`: a run of letters, or a single
+    /// other character as in `\,` or `\{`.
+    fn command_name(&mut self) -> &str {
+        let len = match self.rest.find(|c: char| !c.is_ascii_alphabetic()) {
+            Some(0) => self.peek().map_or(0, char::len_utf8),
+            Some(len) => len,
+            None => self.rest.len(),
+        };
+        let (name, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        name
+    }
+
+    /// Consumes the rest of the formula into an `merror`, once it nests
+    /// deeper than `MAX_DEPTH`.
+    fn too_deep(&mut self) -> HtmlNode {
+        let rest = std::mem::take(&mut self.rest);
+        HtmlNode::el("merror").child(token("mtext", rest))
+    }
+
+    /// Parses atoms, with their sub- and superscripts, up to `end`.
+    fn row(&mut self, end: End) -> Vec<HtmlNode> {
+        if self.depth >= MAX_DEPTH {
+            return vec![self.too_deep()];
+        }
+        self.depth += 1;
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match (self.peek(), end) {
+                (None, _) => break,
+                (Some('}'), End::Brace) | (Some(']'), End::Bracket) => {
+                    self.bump();
+                    break;
+                },
+                // a stray closing brace
+                (Some('}'), _) => {
+                    self.bump();
+                    continue;
+                },
+                _ => {},
+            }
+            if end == End::Right && self.rest.starts_with("\\right") {
+                break;
+            }
+            let (base, limits) = match self.peek() {
+                Some('^' | '_') => (HtmlNode::el("mrow"), false),
+                _ => self.atom(false),
+            };
+            nodes.push(self.scripts(base, limits));
+        }
+        self.depth -= 1;
+        nodes
+    }
+
+    /// Attaches any `_` and `^` that follow `base`.
+    fn scripts(&mut self, base: HtmlNode, limits: bool) -> HtmlNode {
+        let mut sub = None;
+        let mut sup = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('_') if sub.is_none() => {
+                    self.bump();
+                    sub = Some(self.argument());
+                },
+                Some('^') if sup.is_none() => {
+                    self.bump();
+                    sup = Some(self.argument());
+                },
+                // `'` is a prime, shorthand for `^\prime`
+                Some('\'') if sup.is_none() => {
+                    let mut primes = String::new();
+                    while self.peek() == Some('\'') {
+                        self.bump();
+                        primes.push('′');
+                    }
+                    sup = Some(token("mo", &primes));
+                },
+                _ => break,
+            }
+        }
+        let (under, over, both) = if limits && self.display {
+            ("munder", "mover", "munderover")
+        } else {
+            ("msub", "msup", "msubsup")
+        };
+        match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => HtmlNode::el(under).child(base).child(sub),
+            (None, Some(sup)) => HtmlNode::el(over).child(base).child(sup),
+            (Some(sub), Some(sup)) => HtmlNode::el(both).child(base).child(sub).child(sup),
+        }
+    }
+
+    /// The argument of a command or script: a `{group}` or a single atom.
+    fn argument(&mut self) -> HtmlNode {
+        self.skip_whitespace();
+        if self.peek() == Some('{') {
+            self.bump();
+            mrow(self.row(End::Brace))
+        } else if self.peek().is_none() {
+            HtmlNode::el("mrow")
+        } else {
+            self.atom(true).0
+        }
+    }
+
+    /// The raw text of a `{group}` argument, for `\text`.
+    fn text_argument(&mut self) -> String {
+        self.skip_whitespace();
+        if self.peek() != Some('{') {
+            return self.bump().map(String::from).unwrap_or_default();
+        }
+        self.bump();
+        let mut depth = 0;
+        let mut text = String::new();
+        while let Some(c) = self.bump() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                _ => {},
+            }
+            text.push(c);
+        }
+        text
+    }
+
+    /// One atom, and whether it takes its limits above and below. With
+    /// `single`, a number is only its first digit, so `x^23` is `x²3` as in TeX.
+    fn atom(&mut self, single: bool) -> (HtmlNode, bool) {
+        if self.depth >= MAX_DEPTH {
+            return (self.too_deep(), false);
+        }
+        self.depth += 1;
+        let atom = self.read_atom(single);
+        self.depth -= 1;
+        atom
+    }
+
+    fn read_atom(&mut self, single: bool) -> (HtmlNode, bool) {
+        let Some(c) = self.bump() else {
+            return (HtmlNode::el("mrow"), false);
+        };
+        let node = match c {
+            '0'..='9' | '.' if !single => {
+                let len = self.rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(self.rest.len());
+                let number = format!("{c}{}", &self.rest[..len]);
+                self.rest = &self.rest[len..];
+                token("mn", &number)
+            },
+            '0'..='9' => token("mn", &c.to_string()),
+            '{' => mrow(self.row(End::Brace)),
+            '\\' => return self.command(),
+            '-' => token("mo", "−"),
+            '~' => HtmlNode::el("mspace").attr("width", "0.25em"),
+            c if c.is_alphabetic() => token("mi", &c.to_string()),
+            c => token("mo", &c.to_string()),
+        };
+        (node, false)
+    }
+
+    fn command(&mut self) -> (HtmlNode, bool) {
+        let name = self.command_name().to_string();
+        let node = if let Some(symbol) = lookup(GREEK, &name) {
+            token("mi", symbol)
+        } else if let Some(symbol) = lookup(SYMBOLS, &name) {
+            token("mo", symbol)
+        } else if let Some(symbol) = lookup(LARGE_OPERATORS, &name) {
+            let tag = if symbol.chars().all(char::is_alphabetic) || symbol.contains(' ') { "mi" } else { "mo" };
+            return (token(tag, symbol), true);
+        } else if let Some(symbol) = lookup(INTEGRALS, &name) {
+            token("mo", symbol)
+        } else if FUNCTIONS.contains(&name.as_str()) {
+            token("mi", &name)
+        } else if let Some(width) = lookup(SPACES, &name) {
+            HtmlNode::el("mspace").attr("width", width)
+        } else if let Some(accent) = lookup(ACCENTS, &name) {
+            let argument = self.argument();
+            HtmlNode::el("mover").attr("accent", "true").child(argument).child(token("mo", accent))
+        } else {
+            match name.as_str() {
+                "frac" | "dfrac" | "tfrac" => {
+                    let numerator = self.argument();
+                    let denominator = self.argument();
+                    HtmlNode::el("mfrac").child(numerator).child(denominator)
+                },
+                "binom" => {
+                    let top = self.argument();
+                    let bottom = self.argument();
+                    mrow(vec![
+                        token("mo", "("),
+                        HtmlNode::el("mfrac").attr("linethickness", "0").child(top).child(bottom),
+                        token("mo", ")"),
+                    ])
+                },
+                "sqrt" => {
+                    self.skip_whitespace();
+                    if self.peek() == Some('[') {
+                        self.bump();
+                        let index = mrow(self.row(End::Bracket));
+                        HtmlNode::el("mroot").child(self.argument()).child(index)
+                    } else {
+                        HtmlNode::el("msqrt").child(self.argument())
+                    }
+                },
+                "underline" => HtmlNode::el("munder").attr("accentunder", "true")
+                    .child(self.argument())
+                    .child(token("mo", "_")),
+                "text" | "textrm" | "textit" | "textbf" | "mbox" => token("mtext", &self.text_argument()),
+                "mathrm" | "operatorname" => token("mi", &self.text_argument()).attr("mathvariant", "normal"),
+                "mathbf" | "boldsymbol" => self.styled("bold"),
+                "mathit" => self.styled("italic"),
+                "mathbb" => self.styled("double-struck"),
+                "mathcal" => self.styled("script"),
+                "left" => {
+                    let open = self.delimiter();
+                    let mut nodes = vec![token("mo", &open)];
+                    nodes.extend(self.row(End::Right));
+                    if self.rest.starts_with("\\right") {
+                        self.rest = &self.rest["\\right".len()..];
+                    }
+                    nodes.push(token("mo", &self.delimiter()));
+                    HtmlNode::el("mrow").children(nodes)
+                },
+                "right" => token("mo", &self.delimiter()),
+                "\\" => HtmlNode::el("mspace").attr("linebreak", "newline"),
+                "{" | "}" | "%" | "$" | "&" | "_" | "#" | "|" => token("mo", &name),
+                _ => HtmlNode::el("merror").child(token("mtext", &format!("\\{name}"))),
+            }
+        };
+        (node, false)
+    }
+
+    /// The delimiter after `\left` or `\right`; `.` means none.
+    fn delimiter(&mut self) -> String {
+        self.skip_whitespace();
+        match self.bump() {
+            Some('.') | None => String::new(),
+            Some('\\') => {
+                let name = self.command_name().to_string();
+                lookup(SYMBOLS, &name).map_or(name, str::to_string)
+            },
+            Some(c) => c.to_string(),
+        }
+    }
+
+    /// An argument whose identifiers are drawn in `variant`, as in `\mathbf{v}`.
+    fn styled(&mut self, variant: &str) -> HtmlNode {
+        let mut node = self.argument();
+        node.walk_mut(|node| {
+            if node.tag.as_deref() == Some("mi") {
+                node.set_attr("mathvariant", variant);
+            }
+        });
+        node
+    }
+}
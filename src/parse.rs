@@ -0,0 +1,408 @@
+use crate::attributes::Attributes;
+use crate::errors::NodeError;
+use crate::htmlnode::{is_void_element, HtmlNode, NodeKind};
+
+/// Elements whose contents are taken as text up to the matching end tag.
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+/// Like raw text elements, except that character references are decoded.
+const RCDATA_ELEMENTS: [&str; 2] = ["textarea", "title"];
+
+/// Elements whose end tag may be left out.
+const OPTIONAL_END_TAGS: [&str; 13] = [
+    "html", "head", "body", "p", "li", "dt", "dd", "tr", "td", "th", "thead", "tbody", "option",
+];
+
+/// Start tags that implicitly close an open `p`.
+const CLOSES_P: [&str; 27] = [
+    "address", "article", "aside", "blockquote", "details", "div", "dl", "fieldset", "figcaption", "figure",
+    "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "main", "nav", "ol", "p", "pre",
+    "section", "ul",
+];
+
+/// How many elements may be open at once. Start tags nested deeper are
+/// closed straight away, keeping trees shallow enough to serialize and walk
+/// recursively.
+const MAX_DEPTH: usize = 256;
+
+/// Parses an HTML document or fragment into a `NodeKind::Fragment` node.
+///
+/// Like a browser, the parser never gives up: stray end tags are dropped,
+/// unclosed elements are closed at the end of input and elements with
+/// optional end tags (`p`, `li`, `td`, ...) are closed where implied. Use
+/// `parse_html_strict` to treat those repairs as errors.
+pub fn parse_html(input: &str) -> HtmlNode {
+    Parser::new(input).run().0
+}
+
+/// Parses like `parse_html`, but fails on the first problem the tolerant
+/// parser would have had to repair, apart from omitted optional end tags.
+pub fn parse_html_strict(input: &str) -> Result<HtmlNode, NodeError> {
+    let (node, errors) = Parser::new(input).run();
+    match errors.into_iter().next() {
+        Some(error) => Err(NodeError::ParseError(error)),
+        None => Ok(node),
+    }
+}
+
+/// Replaces character references like `&amp;`, `&#39;` and `&#x2014;` with
+/// the characters they stand for. Unknown references are left as they are.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        // references are short, so only look a little way ahead for the `;`
+        let reference = rest.as_bytes()[..rest.len().min(33)].iter()
+            .position(|&b| b == b';')
+            .and_then(|end| decode_reference(&rest[1..end]).map(|c| (c, end)));
+        match reference {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_reference(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return Some(char::from_u32(code).filter(|&c| c != '\0').unwrap_or('\u{FFFD}'));
+    }
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{A0}',
+        "copy" => '©',
+        "reg" => '®',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        _ => return None,
+    };
+    Some(c)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    /// Open elements; the bottom entry is the fragment being built.
+    stack: Vec<HtmlNode>,
+    errors: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser {
+            input,
+            pos: 0,
+            stack: vec![HtmlNode::fragment_node(Vec::new())],
+            errors: Vec::new(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn error(&mut self, message: String) {
+        self.errors.push(format!("{message} at byte {}", self.pos));
+    }
+
+    fn run(mut self) -> (HtmlNode, Vec<String>) {
+        while !self.rest().is_empty() {
+            let rest = self.rest();
+            let after_lt = rest.strip_prefix('<').and_then(|r| r.chars().next());
+            if rest.starts_with("<!--") {
+                self.parse_comment();
+            } else if rest.starts_with("<!") || rest.starts_with("<?") {
+                self.parse_declaration();
+            } else if rest.starts_with("</") && rest[2..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+                self.parse_end_tag();
+            } else if after_lt.is_some_and(|c| c.is_ascii_alphabetic()) {
+                self.parse_start_tag();
+            } else {
+                self.parse_text();
+            }
+        }
+        while self.stack.len() > 1 {
+            let tag = self.current_tag().unwrap_or_default().to_string();
+            if !OPTIONAL_END_TAGS.contains(&tag.as_str()) {
+                self.error(format!("unclosed <{tag}>"));
+            }
+            self.close_current();
+        }
+        let root = self.stack.pop().unwrap_or_else(|| HtmlNode::fragment_node(Vec::new()));
+        (root, self.errors)
+    }
+
+    fn current_tag(&self) -> Option<&str> {
+        self.stack.last().and_then(|node| node.tag.as_deref())
+    }
+
+    fn append(&mut self, node: HtmlNode) {
+        let Some(parent) = self.stack.last_mut() else {
+            return;
+        };
+        // keep text contiguous, e.g. around a stray `<`
+        if node.kind == NodeKind::Text
+            && node.tag.is_none()
+            && let Some(last) = parent.children.last_mut()
+            && last.kind == NodeKind::Text
+            && last.tag.is_none()
+            && let (Some(text), Some(more)) = (last.value.as_mut(), node.value.as_deref())
+        {
+            text.push_str(more);
+            return;
+        }
+        parent.children.push(Box::new(node));
+    }
+
+    fn close_current(&mut self) {
+        if self.stack.len() <= 1 {
+            return;
+        }
+        if let Some(mut node) = self.stack.pop() {
+            // an empty element is a leaf with an empty value in HtmlNode terms
+            if node.children.is_empty() {
+                node.value = Some(String::new());
+            }
+            self.append(node);
+        }
+    }
+
+    /// Closes open elements up to and including the nearest `tag`, as long
+    /// as it sits above any of the `boundaries`.
+    fn close_implied(&mut self, tags: &[&str], boundaries: &[&str]) {
+        let found = self.stack.iter().enumerate().skip(1).rev()
+            .map(|(i, node)| (i, node.tag.as_deref().unwrap_or_default()))
+            .take_while(|(_, tag)| !boundaries.contains(tag))
+            .find(|(_, tag)| tags.contains(tag))
+            .map(|(i, _)| i);
+        if let Some(i) = found {
+            while self.stack.len() > i {
+                self.close_current();
+            }
+        }
+    }
+
+    fn parse_text(&mut self) {
+        // a `<` that doesn't start markup is plain text
+        let start = self.pos;
+        let first_len = self.rest().chars().next().map_or(1, char::len_utf8);
+        let end = self.rest()[first_len..].find('<').map_or(self.input.len(), |i| start + first_len + i);
+        self.pos = end;
+        let text = decode_entities(&self.input[start..end]);
+        self.append(HtmlNode::leaf_node(None, &text, None));
+    }
+
+    fn parse_comment(&mut self) {
+        let body = &self.rest()[4..];
+        let (text, consumed) = match body.find("-->") {
+            Some(end) => (&body[..end], 4 + end + 3),
+            None => {
+                self.error("unterminated comment".to_string());
+                (body, self.rest().len())
+            },
+        };
+        let node = HtmlNode::comment_node(text);
+        self.pos += consumed;
+        self.append(node);
+    }
+
+    fn parse_declaration(&mut self) {
+        let body = &self.rest()[2..];
+        let end = body.find('>').unwrap_or(body.len());
+        let content = body[..end].trim();
+        let node = match content.get(..7) {
+            Some(keyword) if keyword.eq_ignore_ascii_case("doctype") => {
+                HtmlNode::doctype_node(content[7..].trim())
+            },
+            // `<!...>` and `<?...>` that aren't doctypes are bogus comments
+            _ => HtmlNode::comment_node(content),
+        };
+        self.pos = (self.pos + 2 + end + 1).min(self.input.len());
+        self.append(node);
+    }
+
+    fn parse_end_tag(&mut self) {
+        let body = &self.rest()[2..];
+        let name_len = body.find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(body.len());
+        let name = body[..name_len].to_ascii_lowercase();
+        let end = body.find('>').map_or(body.len(), |i| i + 1);
+        self.pos += 2 + end;
+
+        if is_void_element(&name) {
+            return;
+        }
+        let open = self.stack.iter().skip(1).rposition(|node| node.tag.as_deref() == Some(name.as_str()));
+        match open {
+            Some(i) => {
+                // `rposition` counted from after the fragment at the bottom
+                let depth = i + 1;
+                while self.stack.len() > depth + 1 {
+                    let tag = self.current_tag().unwrap_or_default().to_string();
+                    if !OPTIONAL_END_TAGS.contains(&tag.as_str()) {
+                        self.error(format!("<{tag}> closed by </{name}>"));
+                    }
+                    self.close_current();
+                }
+                self.close_current();
+            },
+            None => self.error(format!("stray </{name}>")),
+        }
+    }
+
+    fn parse_start_tag(&mut self) {
+        let body = &self.rest()[1..];
+        let name_len = body.find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(body.len());
+        let name = body[..name_len].to_ascii_lowercase();
+        self.pos += 1 + name_len;
+
+        let (props, self_closing) = self.parse_attributes();
+
+        if CLOSES_P.contains(&name.as_str()) {
+            self.close_implied(&["p"], &["button"]);
+        }
+        match name.as_str() {
+            "li" => self.close_implied(&["li"], &["ul", "ol"]),
+            "dt" | "dd" => self.close_implied(&["dt", "dd"], &["dl"]),
+            "tr" => self.close_implied(&["tr", "td", "th"], &["table", "thead", "tbody"]),
+            "td" | "th" => self.close_implied(&["td", "th"], &["tr", "table"]),
+            "option" => self.close_implied(&["option"], &["select"]),
+            _ => {},
+        }
+
+        let props = (!props.is_empty()).then_some(props);
+        if is_void_element(&name) {
+            self.append(HtmlNode::void_node(&name, props));
+            return;
+        }
+        if self_closing {
+            self.append(HtmlNode::leaf_node(Some(&name), "", props));
+            return;
+        }
+        // the fragment at the bottom of the stack doesn't count
+        if self.stack.len() > MAX_DEPTH {
+            self.error(format!("<{name}> nested more than {MAX_DEPTH} deep"));
+            self.append(HtmlNode::leaf_node(Some(&name), "", props));
+            return;
+        }
+        let raw_text = RAW_TEXT_ELEMENTS.contains(&name.as_str());
+        if raw_text || RCDATA_ELEMENTS.contains(&name.as_str()) {
+            let text = self.take_until_end_tag(&name);
+            let text = if raw_text { text } else { decode_entities(&text) };
+            let child = HtmlNode::leaf_node(None, &text, None);
+            let node = match text.is_empty() {
+                true => HtmlNode::leaf_node(Some(&name), "", props),
+                false => HtmlNode::parent_node(&name, vec![child], props),
+            };
+            self.append(node);
+            return;
+        }
+        self.stack.push(HtmlNode::parent_node(&name, Vec::new(), props));
+    }
+
+    /// Reads attributes up to the end of a start tag, returning them and
+    /// whether the tag ended with `/>`.
+    fn parse_attributes(&mut self) -> (Attributes, bool) {
+        let mut props = Attributes::new();
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.is_empty() {
+                self.error("unterminated start tag".to_string());
+                return (props, false);
+            }
+            if let Some(after) = trimmed.strip_prefix("/>") {
+                self.pos = self.input.len() - after.len();
+                return (props, true);
+            }
+            if let Some(after) = trimmed.strip_prefix('>') {
+                self.pos = self.input.len() - after.len();
+                return (props, false);
+            }
+            if let Some(after) = trimmed.strip_prefix('/') {
+                self.pos = self.input.len() - after.len();
+                continue;
+            }
+
+            let name_len = trimmed.find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+                .unwrap_or(trimmed.len())
+                .max(1);
+            let name = trimmed[..name_len].to_ascii_lowercase();
+            self.pos += name_len;
+
+            let rest = self.rest();
+            let after_name = rest.trim_start();
+            let value = match after_name.strip_prefix('=') {
+                Some(after_eq) => {
+                    let after_eq = after_eq.trim_start();
+                    let (value, consumed) = match after_eq.chars().next() {
+                        Some(quote @ ('"' | '\'')) => match after_eq[1..].find(quote) {
+                            Some(end) => (&after_eq[1..1 + end], 1 + end + 1),
+                            None => (&after_eq[1..], after_eq.len()),
+                        },
+                        _ => {
+                            let end = after_eq.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(after_eq.len());
+                            (&after_eq[..end], end)
+                        },
+                    };
+                    self.pos = self.input.len() - after_eq.len() + consumed;
+                    Some(decode_entities(value))
+                },
+                None => None,
+            };
+            // as in browsers, the first occurrence of an attribute wins
+            if props.contains(&name) {
+                continue;
+            }
+            match value {
+                Some(value) => props.insert(&name, &value),
+                None => props.insert_bool(&name),
+            }
+        }
+    }
+
+    /// Consumes text up to the end tag for `name` (case-insensitively) and
+    /// the end tag itself.
+    fn take_until_end_tag(&mut self, name: &str) -> String {
+        let rest = self.rest();
+        let closing = format!("</{name}");
+        let end = rest.char_indices()
+            .map(|(i, _)| i)
+            .find(|&i| rest[i..].get(..closing.len()).is_some_and(|s| s.eq_ignore_ascii_case(&closing)));
+        match end {
+            Some(end) => {
+                let text = rest[..end].to_string();
+                let close = rest[end..].find('>').map_or(rest.len(), |i| end + i + 1);
+                self.pos += close;
+                text
+            },
+            None => {
+                self.error(format!("unclosed <{name}>"));
+                self.pos = self.input.len();
+                rest.to_string()
+            },
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+
+use crate::attributes::Attributes;
+use crate::errors::NodeError;
+use crate::htmlnode::HtmlNode;
+use crate::images::{find_asset, image_dimensions, site_path, ImageOptions};
+use crate::visit::VisitorMut;
+
+/// Settings for `add_responsive_images`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponsiveOptions {
+    /// Widths, in pixels, of the resized copies. Widths at or above an
+    /// image's own width are skipped.
+    pub widths: Vec<u32>,
+    /// Also offer WebP copies through a `<source>` in a `<picture>`.
+    pub webp: bool,
+    /// The `sizes` attribute: how wide the image is displayed.
+    pub sizes: String,
+    /// Where the site is written. Copies go next to the published original.
+    pub output_dir: PathBuf,
+    /// Encoded copies kept between builds, keyed on the source image's
+    /// contents, so unchanged images aren't resized again.
+    pub cache_dir: PathBuf,
+}
+
+impl Default for ResponsiveOptions {
+    fn default() -> Self {
+        ResponsiveOptions {
+            widths: vec![480, 800],
+            webp: true,
+            sizes: "(max-width: 800px) 100vw, 800px".to_string(),
+            output_dir: PathBuf::from("docs"),
+            cache_dir: PathBuf::from(".cache/images"),
+        }
+    }
+}
+
+/// Gives every local PNG or JPEG `img` in the tree a `srcset` of resized
+/// copies, written to `output_dir`, and wraps it in a `picture` offering
+/// WebP copies when enabled. Images that can't be found are left alone, as
+/// are those that can't be decoded; their URLs are returned.
+pub fn add_responsive_images(node: &mut HtmlNode, page_dir: &Path, images: &ImageOptions, options: &ResponsiveOptions) -> Result<Vec<String>, NodeError> {
+    let mut visitor = Responsive { page_dir, images, options, failed: Vec::new(), error: None };
+    node.accept_mut(&mut visitor);
+    match visitor.error {
+        Some(e) => Err(e),
+        None => Ok(visitor.failed),
+    }
+}
+
+struct Responsive<'a> {
+    page_dir: &'a Path,
+    images: &'a ImageOptions,
+    options: &'a ResponsiveOptions,
+    failed: Vec<String>,
+    /// The first error that isn't the image's fault, which stops the pass.
+    error: Option<NodeError>,
+}
+
+impl VisitorMut for Responsive<'_> {
+    fn enter(&mut self, node: &mut HtmlNode) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        match node.tag.as_deref() {
+            // already responsive, written by hand
+            Some("picture") => false,
+            Some("img") => {
+                match responsive_image(node, self.page_dir, self.images, self.options) {
+                    Ok(Some(replacement)) => *node = replacement,
+                    Ok(None) => {},
+                    Err(NodeError::ImageError(_)) => self.failed.push(node.get_attr("src").unwrap_or_default().to_string()),
+                    Err(e) => self.error = Some(e),
+                }
+                false
+            },
+            _ => true,
+        }
+    }
+}
+
+/// A source image and the copies made of it.
+struct Source<'a> {
+    bytes: Vec<u8>,
+    decoded: Option<DynamicImage>,
+    key: u64,
+    /// The URL of the original, up to and including the last `/`.
+    url_dir: &'a str,
+    /// The start of the copies' names: the original's name with its
+    /// extension kept, so `photo.png` and `photo.jpg` don't share copies.
+    stem: String,
+    out_dir: PathBuf,
+}
+
+impl Source<'_> {
+    fn decoded(&mut self) -> Result<&DynamicImage, NodeError> {
+        if self.decoded.is_none() {
+            self.decoded = Some(image::load_from_memory(&self.bytes)?);
+        }
+        Ok(self.decoded.as_ref().expect("decoded above"))
+    }
+
+    /// Writes a copy `width` pixels wide, reusing a cached encoding when there
+    /// is one, and returns its `srcset` candidate.
+    fn variant(&mut self, width: u32, format: ImageFormat, options: &ResponsiveOptions) -> Result<String, NodeError> {
+        let ext = format.extensions_str()[0];
+        let name = format!("{}-{width}w.{ext}", self.stem);
+        let dest = self.out_dir.join(&name);
+        if !dest.is_file() {
+            let cached = options.cache_dir.join(format!("{:016x}-{width}w.{ext}", self.key));
+            if !cached.is_file() {
+                let image = self.decoded()?;
+                let height = (image.height() as u64 * width as u64).div_ceil(image.width() as u64) as u32;
+                let mut resized = image.resize_exact(width, height.max(1), FilterType::Lanczos3);
+                if format == ImageFormat::WebP {
+                    resized = DynamicImage::from(resized.to_rgba8());
+                }
+                let mut encoded = Cursor::new(Vec::new());
+                resized.write_to(&mut encoded, format)?;
+                std::fs::create_dir_all(&options.cache_dir)?;
+                std::fs::write(&cached, encoded.into_inner())?;
+            }
+            std::fs::create_dir_all(&self.out_dir)?;
+            std::fs::copy(&cached, &dest)?;
+        }
+        Ok(format!("{}{name} {width}w", self.url_dir))
+    }
+}
+
+fn responsive_image(img: &HtmlNode, page_dir: &Path, images: &ImageOptions, options: &ResponsiveOptions) -> Result<Option<HtmlNode>, NodeError> {
+    let Some(src) = img.get_attr("src") else {
+        return Ok(None);
+    };
+    let Some(path) = site_path(src, page_dir) else {
+        return Ok(None);
+    };
+    let format = match ImageFormat::from_path(&path) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Jpeg)) => format,
+        _ => return Ok(None),
+    };
+    let Some(file) = find_asset(src, page_dir, &images.roots) else {
+        return Ok(None);
+    };
+    let bytes = std::fs::read(&file)?;
+    let Some((width, _)) = image_dimensions(&bytes) else {
+        return Ok(None);
+    };
+    let mut widths: Vec<u32> = options.widths.iter().copied().filter(|&w| w > 0 && w < width).collect();
+    widths.sort_unstable();
+    widths.dedup();
+    if widths.is_empty() && !options.webp {
+        return Ok(None);
+    }
+
+    let url = src.split(['?', '#']).next().unwrap_or_default();
+    let mut source = Source {
+        key: fnv1a(&bytes),
+        bytes,
+        decoded: None,
+        url_dir: &url[..url.rfind('/').map_or(0, |i| i + 1)],
+        stem: format!(
+            "{}-{}",
+            path.file_stem().and_then(|s| s.to_str()).unwrap_or("image"),
+            path.extension().and_then(|s| s.to_str()).unwrap_or_default(),
+        ),
+        out_dir: options.output_dir.join(path.parent().unwrap_or(Path::new(""))),
+    };
+
+    let mut srcset = Vec::new();
+    for &w in &widths {
+        srcset.push(source.variant(w, format, options)?);
+    }
+    srcset.push(format!("{src} {width}w"));
+    let img = img.clone()
+        .attr("srcset", srcset.join(", "))
+        .attr("sizes", &options.sizes);
+    if !options.webp {
+        return Ok(Some(img));
+    }
+
+    let mut webp_srcset = Vec::new();
+    for &w in widths.iter().chain([&width]) {
+        webp_srcset.push(source.variant(w, ImageFormat::WebP, options)?);
+    }
+    let webp = HtmlNode::void_node("source", Some(Attributes::from([
+        ("type", "image/webp".to_string()),
+        ("srcset", webp_srcset.join(", ")),
+        ("sizes", options.sizes.clone()),
+    ])));
+    Ok(Some(HtmlNode::el("picture").child(webp).child(img)))
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher` its output is fixed, so cache keys
+/// stay valid across Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
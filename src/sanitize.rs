@@ -0,0 +1,201 @@
+use crate::attributes::Attributes;
+use crate::htmlnode::{HtmlNode, NodeKind};
+use crate::parse::parse_html;
+
+/// Attributes holding a URL that is followed as a link.
+const LINK_ATTRIBUTES: &[&str] = &["href", "action", "formaction", "cite"];
+
+/// Attributes holding a URL that the browser fetches on its own.
+const RESOURCE_ATTRIBUTES: &[&str] = &["src", "srcset", "poster"];
+
+/// What survives `SanitizePolicy::sanitize`.
+///
+/// The default policy trusts the page's author and only removes URLs with
+/// schemes outside the allowed lists, so `[x](javascript:alert(1))` loses its
+/// `href`. `SanitizePolicy::strict` also limits raw HTML to an allowlist of
+/// tags and attributes, for content from people you don't know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanitizePolicy {
+    /// Schemes allowed in `href` and other link attributes. Relative URLs are
+    /// always allowed.
+    pub link_schemes: Vec<String>,
+    /// Schemes allowed in `src`, `srcset` and `poster`.
+    pub resource_schemes: Vec<String>,
+    /// Elements that are kept. Any other element is replaced by its content;
+    /// `None` keeps every element.
+    pub allowed_tags: Option<Vec<String>>,
+    /// Attributes that are kept; `None` keeps every attribute.
+    pub allowed_attributes: Option<Vec<String>>,
+    /// Elements that are removed together with everything inside them.
+    pub removed_tags: Vec<String>,
+    /// Remove HTML comments and doctypes.
+    pub strip_comments: bool,
+}
+
+fn strings(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+fn contains(list: &[String], name: &str) -> bool {
+    list.iter().any(|item| item.eq_ignore_ascii_case(name))
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> Self {
+        SanitizePolicy {
+            link_schemes: strings(&["http", "https", "mailto", "tel"]),
+            resource_schemes: strings(&["http", "https"]),
+            allowed_tags: None,
+            allowed_attributes: None,
+            removed_tags: Vec::new(),
+            strip_comments: false,
+        }
+    }
+}
+
+impl SanitizePolicy {
+    /// A policy for untrusted content: formatting, lists, tables, links and
+    /// images only, without scripts, styles, embeds or event handlers.
+    pub fn strict() -> Self {
+        SanitizePolicy {
+            allowed_tags: Some(strings(&[
+                "a", "abbr", "b", "blockquote", "br", "code", "dd", "del", "details", "div", "dl", "dt",
+                "em", "figcaption", "figure", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "i", "img",
+                "ins", "kbd", "li", "mark", "ol", "p", "pre", "q", "s", "small", "span", "strong",
+                "sub", "summary", "sup", "table", "tbody", "td", "tfoot", "th", "thead", "tr", "u", "ul",
+                // MathML, as written for `$formulas$`
+                "math", "merror", "mfrac", "mi", "mn", "mo", "mover", "mroot", "mrow", "mspace", "msqrt",
+                "msub", "msubsup", "msup", "mtext", "munder", "munderover",
+            ])),
+            allowed_attributes: Some(strings(&[
+                "alt", "aria-hidden", "aria-label", "cite", "class", "colspan", "dir", "height", "href",
+                "id", "lang", "rowspan", "src", "start", "title", "width",
+                "accent", "accentunder", "display", "linebreak", "linethickness", "mathvariant",
+            ])),
+            removed_tags: strings(&[
+                "script", "style", "iframe", "object", "embed", "template", "noscript", "frame", "frameset",
+            ]),
+            strip_comments: true,
+            ..SanitizePolicy::default()
+        }
+    }
+
+    /// Cleans every descendant of `node` according to the policy. `node`
+    /// itself is kept as it is, apart from its attributes.
+    ///
+    /// Raw (`NodeKind::Raw`) nodes are parsed so their markup can be checked
+    /// like everything else. Returns the URLs removed for having a scheme the
+    /// policy doesn't allow.
+    pub fn sanitize(&self, node: &mut HtmlNode) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.sanitize_into(node, &mut removed);
+        removed
+    }
+
+    fn sanitize_into(&self, node: &mut HtmlNode, removed: &mut Vec<String>) {
+        self.clean_attributes(node, removed);
+        for mut child in std::mem::take(&mut node.children) {
+            match child.kind {
+                NodeKind::Comment | NodeKind::Doctype if self.strip_comments => {},
+                NodeKind::Comment | NodeKind::Doctype => node.children.push(child),
+                NodeKind::Raw => {
+                    let mut parsed = parse_html(child.value.as_deref().unwrap_or_default());
+                    self.sanitize_into(&mut parsed, removed);
+                    node.children.push(Box::new(parsed));
+                },
+                NodeKind::Fragment => {
+                    self.sanitize_into(&mut child, removed);
+                    node.children.push(child);
+                },
+                NodeKind::Text => match child.tag.clone() {
+                    None => node.children.push(child),
+                    Some(tag) if contains(&self.removed_tags, &tag) => {},
+                    Some(tag) if self.allowed_tags.as_ref().is_none_or(|tags| contains(tags, &tag)) => {
+                        self.sanitize_into(&mut child, removed);
+                        node.children.push(child);
+                    },
+                    Some(_) => {
+                        // keep the content of an element we don't allow
+                        self.sanitize_into(&mut child, removed);
+                        if child.children.is_empty() {
+                            if let Some(text) = child.value.filter(|text| !text.is_empty()) {
+                                node.children.push(Box::new(HtmlNode::leaf_node(None, &text, None)));
+                            }
+                        } else {
+                            node.children.append(&mut child.children);
+                        }
+                    },
+                },
+            }
+        }
+    }
+
+    fn clean_attributes(&self, node: &mut HtmlNode, removed: &mut Vec<String>) {
+        if node.tag.is_none() {
+            return;
+        }
+        let Some(props) = node.props.take() else {
+            return;
+        };
+        let mut kept = Attributes::new();
+        for (name, value) in props.iter() {
+            if let Some(allowed) = &self.allowed_attributes && !contains(allowed, name) {
+                continue;
+            }
+            let is_one_of = |list: &[&str]| list.iter().any(|item| item.eq_ignore_ascii_case(name));
+            let schemes = if is_one_of(LINK_ATTRIBUTES) {
+                Some(&self.link_schemes)
+            } else if is_one_of(RESOURCE_ATTRIBUTES) {
+                Some(&self.resource_schemes)
+            } else {
+                None
+            };
+            match (value, schemes) {
+                (Some(url), Some(schemes)) => {
+                    let urls: Vec<&str> = if name.eq_ignore_ascii_case("srcset") {
+                        srcset_urls(url).collect()
+                    } else {
+                        vec![url]
+                    };
+                    if urls.into_iter().all(|url| is_allowed_url(url, schemes)) {
+                        kept.insert(name, url);
+                    } else {
+                        removed.push(url.to_string());
+                    }
+                },
+                (Some(value), None) => kept.insert(name, value),
+                (None, _) => kept.insert_bool(name),
+            }
+        }
+        node.props = (!kept.is_empty()).then_some(kept);
+    }
+}
+
+/// The scheme of `url` in lower case, such as `https` or `javascript`, or
+/// `None` for a relative URL.
+///
+/// Like a browser, this ignores leading spaces and control characters and
+/// tabs or newlines anywhere, so `" java\tscript:"` is still `javascript`.
+pub fn url_scheme(url: &str) -> Option<String> {
+    let url: String = url.trim_start_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    let end = url.find([':', '/', '?', '#'])?;
+    let scheme = &url[..end];
+    let is_scheme = url[end..].starts_with(':')
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    is_scheme.then(|| scheme.to_ascii_lowercase())
+}
+
+/// Whether `url` is relative or uses one of `schemes`.
+pub fn is_allowed_url(url: &str, schemes: &[String]) -> bool {
+    url_scheme(url).is_none_or(|scheme| contains(schemes, &scheme))
+}
+
+/// The URLs in a `srcset` value such as `a.png 1x, b.png 2x`.
+fn srcset_urls(srcset: &str) -> impl Iterator<Item = &str> {
+    srcset.split(',')
+        .filter_map(|candidate| candidate.split_whitespace().next())
+}
@@ -0,0 +1,408 @@
+use std::collections::HashSet;
+
+use crate::errors::NodeError;
+use crate::htmlnode::HtmlNode;
+
+/// How an attribute selector compares the attribute's value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AttrOp {
+    /// `[name]`
+    Exists,
+    /// `[name=value]`
+    Equals(String),
+    /// `[name^=value]`
+    StartsWith(String),
+    /// `[name$=value]`
+    EndsWith(String),
+    /// `[name*=value]`
+    Contains(String),
+    /// `[name~=value]`, one of a whitespace-separated list
+    Word(String),
+    /// `[name|=value]`, exactly `value` or starting with `value-`
+    Prefix(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AttrSelector {
+    name: String,
+    op: AttrOp,
+}
+
+impl AttrSelector {
+    fn matches(&self, node: &HtmlNode) -> bool {
+        let Some(value) = node.get_attr(&self.name) else {
+            return false;
+        };
+        match &self.op {
+            AttrOp::Exists => true,
+            AttrOp::Equals(v) => value == v,
+            AttrOp::StartsWith(v) => !v.is_empty() && value.starts_with(v.as_str()),
+            AttrOp::EndsWith(v) => !v.is_empty() && value.ends_with(v.as_str()),
+            AttrOp::Contains(v) => !v.is_empty() && value.contains(v.as_str()),
+            AttrOp::Word(v) => value.split_whitespace().any(|w| w == v),
+            AttrOp::Prefix(v) => value == v || value.strip_prefix(v.as_str()).is_some_and(|rest| rest.starts_with('-')),
+        }
+    }
+}
+
+/// A run of simple selectors with no combinator between them, like `a.external[href]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Compound {
+    tag: Option<String>,
+    ids: Vec<String>,
+    classes: Vec<String>,
+    attrs: Vec<AttrSelector>,
+    first_child: bool,
+}
+
+impl Compound {
+    fn is_empty(&self) -> bool {
+        self == &Compound::default()
+    }
+
+    fn matches(&self, node: &HtmlNode, is_first_child: bool) -> bool {
+        let Some(tag) = &node.tag else {
+            return false;
+        };
+        self.tag.as_ref().is_none_or(|t| t.eq_ignore_ascii_case(tag))
+            && self.ids.iter().all(|id| node.get_attr("id") == Some(id.as_str()))
+            && self.classes.iter().all(|class| node.has_class(class))
+            && self.attrs.iter().all(|attr| attr.matches(node))
+            && (!self.first_child || is_first_child)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    /// `a b`
+    Descendant,
+    /// `a > b`
+    Child,
+}
+
+/// Compound selectors joined by combinators; `combinators[i]` sits between
+/// `compounds[i]` and `compounds[i + 1]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Complex {
+    compounds: Vec<Compound>,
+    combinators: Vec<Combinator>,
+}
+
+/// An element node above the one being matched, and whether it is the first
+/// element among its siblings.
+type Ancestor<'a> = (&'a HtmlNode, bool);
+
+impl Complex {
+    fn matches(&self, node: &HtmlNode, is_first_child: bool, ancestors: &[Ancestor]) -> bool {
+        self.matches_at(self.compounds.len() - 1, node, is_first_child, ancestors)
+    }
+
+    fn matches_at(&self, i: usize, node: &HtmlNode, is_first_child: bool, ancestors: &[Ancestor]) -> bool {
+        if !self.compounds[i].matches(node, is_first_child) {
+            return false;
+        }
+        if i == 0 {
+            return true;
+        }
+        match self.combinators[i - 1] {
+            Combinator::Child => match ancestors.split_last() {
+                Some((&(parent, first), rest)) => self.matches_at(i - 1, parent, first, rest),
+                None => false,
+            },
+            Combinator::Descendant => (0..ancestors.len()).rev().any(|j| {
+                let (ancestor, first) = ancestors[j];
+                self.matches_at(i - 1, ancestor, first, &ancestors[..j])
+            }),
+        }
+    }
+}
+
+/// A parsed CSS selector list such as `h2 > code, a[href^="/"]`.
+///
+/// Supports type, universal, class, id and attribute selectors, the
+/// descendant and child combinators, and `:first-child`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    alternatives: Vec<Complex>,
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Selector, NodeError> {
+        Parser { chars: selector.chars().collect(), pos: 0, source: selector }.parse_list()
+    }
+
+    /// Whether `node` matches, given its ancestors from the root down.
+    fn matches(&self, node: &HtmlNode, is_first_child: bool, ancestors: &[Ancestor]) -> bool {
+        self.alternatives.iter().any(|complex| complex.matches(node, is_first_child, ancestors))
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    source: &'a str,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> NodeError {
+        NodeError::ParseError(format!("Invalid selector {:?} at {}: {}", self.source, self.pos, reason))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    fn parse_list(&mut self) -> Result<Selector, NodeError> {
+        let mut alternatives = vec![self.parse_complex()?];
+        while self.peek() == Some(',') {
+            self.pos += 1;
+            alternatives.push(self.parse_complex()?);
+        }
+        if self.peek().is_some() {
+            return Err(self.error("unexpected character"));
+        }
+        Ok(Selector { alternatives })
+    }
+
+    fn parse_complex(&mut self) -> Result<Complex, NodeError> {
+        self.skip_whitespace();
+        let mut compounds = vec![self.parse_compound()?];
+        let mut combinators = Vec::new();
+        loop {
+            let had_space = self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some('>') => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    Combinator::Child
+                },
+                Some(',') | None => break,
+                Some(_) if had_space => Combinator::Descendant,
+                Some(_) => return Err(self.error("unexpected character")),
+            };
+            combinators.push(combinator);
+            compounds.push(self.parse_compound()?);
+        }
+        Ok(Complex { compounds, combinators })
+    }
+
+    fn parse_compound(&mut self) -> Result<Compound, NodeError> {
+        let mut compound = Compound::default();
+        let mut universal = false;
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                universal = true;
+            },
+            Some(c) if is_ident_char(c) => compound.tag = Some(self.parse_ident()?),
+            _ => {},
+        }
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.pos += 1;
+                    compound.ids.push(self.parse_ident()?);
+                },
+                Some('.') => {
+                    self.pos += 1;
+                    compound.classes.push(self.parse_ident()?);
+                },
+                Some('[') => {
+                    self.pos += 1;
+                    compound.attrs.push(self.parse_attr()?);
+                },
+                Some(':') => {
+                    self.pos += 1;
+                    match self.parse_ident()?.as_str() {
+                        "first-child" => compound.first_child = true,
+                        _ => return Err(self.error("unsupported pseudo-class")),
+                    }
+                },
+                _ => break,
+            }
+        }
+        if compound.is_empty() && !universal {
+            return Err(self.error("expected a selector"));
+        }
+        Ok(compound)
+    }
+
+    fn parse_ident(&mut self) -> Result<String, NodeError> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_ident_char) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("expected a name"));
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn parse_attr(&mut self) -> Result<AttrSelector, NodeError> {
+        self.skip_whitespace();
+        let name = self.parse_ident()?;
+        self.skip_whitespace();
+        let op = match self.peek() {
+            Some(']') => {
+                self.pos += 1;
+                return Ok(AttrSelector { name, op: AttrOp::Exists });
+            },
+            Some('=') => {
+                self.pos += 1;
+                '='
+            },
+            Some(c @ ('^' | '$' | '*' | '~' | '|')) if self.chars.get(self.pos + 1) == Some(&'=') => {
+                self.pos += 2;
+                c
+            },
+            _ => return Err(self.error("expected an attribute operator")),
+        };
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != quote) {
+                    self.pos += 1;
+                }
+                if self.peek().is_none() {
+                    return Err(self.error("unterminated string"));
+                }
+                let value: String = self.chars[start..self.pos].iter().collect();
+                self.pos += 1;
+                value
+            },
+            _ => self.parse_ident()?,
+        };
+        self.skip_whitespace();
+        if self.peek() != Some(']') {
+            return Err(self.error("expected `]`"));
+        }
+        self.pos += 1;
+        let op = match op {
+            '=' => AttrOp::Equals(value),
+            '^' => AttrOp::StartsWith(value),
+            '$' => AttrOp::EndsWith(value),
+            '*' => AttrOp::Contains(value),
+            '~' => AttrOp::Word(value),
+            _ => AttrOp::Prefix(value),
+        };
+        Ok(AttrSelector { name, op })
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii()
+}
+
+impl HtmlNode {
+    /// Every node in the tree, this one included, matching `selector`, in
+    /// document order.
+    pub fn select(&self, selector: &Selector) -> Vec<&HtmlNode> {
+        let mut found = Vec::new();
+        let mut ancestors = Vec::new();
+        self.select_ref(selector, false, &mut ancestors, &mut found);
+        found
+    }
+
+    /// Like `select`, parsing the selector first.
+    pub fn query(&self, selector: &str) -> Result<Vec<&HtmlNode>, NodeError> {
+        Ok(self.select(&Selector::parse(selector)?))
+    }
+
+    /// Mutable references to the outermost nodes matching `selector`. A
+    /// match nested inside another match is left out, since both can't be
+    /// borrowed mutably at once; use `for_each_selected_mut` to reach those.
+    pub fn select_mut(&mut self, selector: &Selector) -> Vec<&mut HtmlNode> {
+        let paths: HashSet<Vec<usize>> = self.selected_paths(selector).into_iter().collect();
+        let mut found = Vec::new();
+        let mut path = Vec::new();
+        self.collect_paths_mut(&paths, &mut path, &mut found);
+        found
+    }
+
+    /// Calls `f` on every node matching `selector`, nested matches included.
+    /// Matches are visited in reverse document order so that `f` can rewrite
+    /// a node's children without disturbing the matches still to come.
+    pub fn for_each_selected_mut<F: FnMut(&mut HtmlNode)>(&mut self, selector: &Selector, mut f: F) {
+        for path in self.selected_paths(selector).into_iter().rev() {
+            if let Some(node) = self.node_at_path_mut(&path) {
+                f(node);
+            }
+        }
+    }
+
+    /// Child-index paths, from this node, of every match in document order.
+    fn selected_paths(&self, selector: &Selector) -> Vec<Vec<usize>> {
+        let mut paths = Vec::new();
+        let mut path = Vec::new();
+        self.paths_ref(selector, false, &mut Vec::new(), &mut path, &mut paths);
+        paths
+    }
+
+    fn select_ref<'a>(
+        &'a self,
+        selector: &Selector,
+        is_first_child: bool,
+        ancestors: &mut Vec<Ancestor<'a>>,
+        found: &mut Vec<&'a HtmlNode>,
+    ) {
+        if selector.matches(self, is_first_child, ancestors) {
+            found.push(self);
+        }
+        ancestors.push((self, is_first_child));
+        let first_element = self.children.iter().position(|c| c.tag.is_some());
+        for (i, child) in self.children.iter().enumerate() {
+            child.select_ref(selector, Some(i) == first_element, ancestors, found);
+        }
+        ancestors.pop();
+    }
+
+    fn paths_ref<'a>(
+        &'a self,
+        selector: &Selector,
+        is_first_child: bool,
+        ancestors: &mut Vec<Ancestor<'a>>,
+        path: &mut Vec<usize>,
+        paths: &mut Vec<Vec<usize>>,
+    ) {
+        if selector.matches(self, is_first_child, ancestors) {
+            paths.push(path.clone());
+        }
+        ancestors.push((self, is_first_child));
+        let first_element = self.children.iter().position(|c| c.tag.is_some());
+        for (i, child) in self.children.iter().enumerate() {
+            path.push(i);
+            child.paths_ref(selector, Some(i) == first_element, ancestors, path, paths);
+            path.pop();
+        }
+        ancestors.pop();
+    }
+
+    fn collect_paths_mut<'a>(&'a mut self, paths: &HashSet<Vec<usize>>, path: &mut Vec<usize>, found: &mut Vec<&'a mut HtmlNode>) {
+        if paths.contains(path) {
+            found.push(self);
+            return;
+        }
+        for (i, child) in self.children.iter_mut().enumerate() {
+            path.push(i);
+            child.collect_paths_mut(paths, path, found);
+            path.pop();
+        }
+    }
+
+    fn node_at_path_mut(&mut self, path: &[usize]) -> Option<&mut HtmlNode> {
+        match path.split_first() {
+            None => Some(self),
+            Some((&i, rest)) => self.children.get_mut(i)?.node_at_path_mut(rest),
+        }
+    }
+}
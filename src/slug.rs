@@ -0,0 +1,66 @@
+use std::collections::HashSet;
+
+/// Turns heading text into a URL fragment the way GitHub does: lowercase,
+/// letters and digits from any script kept, whitespace turned into hyphens
+/// and other punctuation dropped.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() || c == '-' || c == '_' {
+            slug.push(c);
+        } else if c.is_whitespace() {
+            slug.push('-');
+        }
+    }
+    slug
+}
+
+/// Hands out unique heading ids for a single page, suffixing repeats with
+/// `-1`, `-2`, ... so `section` is followed by `section-1`. Ids an author
+/// asked for with `reserve` are never handed out to other headings.
+#[derive(Debug, Default)]
+pub struct Slugger {
+    seen: HashSet<String>,
+    reserved: HashSet<String>,
+}
+
+impl Slugger {
+    pub fn new() -> Self {
+        Slugger::default()
+    }
+
+    /// Sets aside an explicit id before the page's headings are numbered, so
+    /// a generated id that comes earlier can't take it.
+    pub fn reserve(&mut self, id: &str) {
+        self.reserved.insert(id.to_string());
+    }
+
+    /// Returns a unique id derived from `text`.
+    pub fn slug(&mut self, text: &str) -> String {
+        let base = match slugify(text) {
+            s if s.is_empty() => "section".to_string(),
+            s => s,
+        };
+        self.unique(&base)
+    }
+
+    /// Reserves `id` as given, falling back to a suffixed variant if another
+    /// heading already has it.
+    pub fn claim(&mut self, id: &str) -> String {
+        if self.seen.insert(id.to_string()) {
+            return id.to_string();
+        }
+        self.unique(id)
+    }
+
+    fn unique(&mut self, base: &str) -> String {
+        let mut candidate = base.to_string();
+        let mut n = 0;
+        while self.seen.contains(&candidate) || self.reserved.contains(&candidate) {
+            n += 1;
+            candidate = format!("{base}-{n}");
+        }
+        self.seen.insert(candidate.clone());
+        candidate
+    }
+}
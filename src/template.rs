@@ -0,0 +1,111 @@
+use crate::htmlnode::{HtmlNode, NodeKind};
+
+/// A placeholder and the node that replaces it.
+struct Slot<'a> {
+    name: &'a str,
+    /// Moved into the last place that uses it and copied into the others.
+    node: Option<HtmlNode>,
+    /// Places in text that are still to be filled.
+    uses: usize,
+    /// The node's text, for placeholders in attribute values.
+    text: Option<String>,
+}
+
+impl Slot<'_> {
+    fn fill(&mut self) -> HtmlNode {
+        self.uses = self.uses.saturating_sub(1);
+        let node = if self.uses == 0 { self.node.take() } else { self.node.clone() };
+        node.unwrap_or_else(|| HtmlNode::text_node(""))
+    }
+}
+
+/// Fills the `{{ Name }}` placeholders of a parsed page template.
+///
+/// A placeholder in text is replaced by its node, so the page's content is
+/// inserted as a tree rather than pasted in as a string. Nodes are moved into
+/// the page and only copied when their placeholder appears more than once.
+/// In an attribute value a placeholder is replaced by the node's text.
+pub fn fill_template(template: &mut HtmlNode, slots: Vec<(&str, HtmlNode)>) {
+    let mut slots: Vec<Slot> = slots.into_iter()
+        .map(|(name, node)| Slot { name, node: Some(node), uses: 0, text: None })
+        .collect();
+    template.walk(|node| {
+        for slot in slots.iter_mut() {
+            if node.kind == NodeKind::Text && node.children.is_empty() && let Some(text) = node.value.as_deref() {
+                slot.uses += text.matches(slot.name).count();
+            }
+            let in_attribute = node.props.iter()
+                .flat_map(|props| props.iter())
+                .any(|(_, value)| value.is_some_and(|value| value.contains(slot.name)));
+            if in_attribute && slot.text.is_none() {
+                slot.text = slot.node.as_ref().map(HtmlNode::text_content);
+            }
+        }
+    });
+    fill_node(template, &mut slots);
+}
+
+fn fill_node(template: &mut HtmlNode, slots: &mut [Slot]) {
+    if let Some(props) = &template.props {
+        let filled: Vec<(String, String)> = props.iter()
+            .filter_map(|(key, value)| Some((key, value?)))
+            .filter(|(_, value)| slots.iter().any(|slot| value.contains(slot.name)))
+            .map(|(key, value)| {
+                let value = slots.iter().fold(value.to_string(), |value, slot| {
+                    value.replace(slot.name, slot.text.as_deref().unwrap_or_default())
+                });
+                (key.to_string(), value)
+            })
+            .collect();
+        for (key, value) in filled {
+            template.set_attr(&key, &value);
+        }
+    }
+
+    // an element that holds its text in `value`, like a parsed `<title>`
+    if template.tag.is_some() && template.children.is_empty() && template.kind == NodeKind::Text {
+        if let Some(text) = template.value.take() {
+            template.children = fill_text(&text, slots).into_iter().map(Box::new).collect();
+            if template.children.is_empty() {
+                template.value = Some(text);
+            }
+        }
+        return;
+    }
+
+    let mut children = Vec::new();
+    for mut child in std::mem::take(&mut template.children) {
+        let is_text = child.tag.is_none() && child.kind == NodeKind::Text;
+        match child.value.as_deref() {
+            Some(text) if is_text && slots.iter().any(|slot| text.contains(slot.name)) => {
+                children.extend(fill_text(text, slots).into_iter().map(Box::new));
+            },
+            _ => {
+                fill_node(&mut child, slots);
+                children.push(child);
+            },
+        }
+    }
+    template.children = children;
+}
+
+/// Splits `text` around the placeholders in it. Empty if `text` has none.
+fn fill_text(text: &str, slots: &mut [Slot]) -> Vec<HtmlNode> {
+    let mut nodes = Vec::new();
+    let mut rest = text;
+    while let Some((start, i)) = slots.iter()
+        .enumerate()
+        .filter_map(|(i, slot)| Some((rest.find(slot.name)?, i)))
+        .min()
+    {
+        if start > 0 {
+            nodes.push(HtmlNode::text_node(&rest[..start]));
+        }
+        nodes.push(slots[i].fill());
+        rest = &rest[start + slots[i].name.len()..];
+    }
+    if !nodes.is_empty() && !rest.is_empty() {
+        nodes.push(HtmlNode::text_node(rest));
+    }
+    nodes
+}
@@ -1,10 +1,51 @@
 #[cfg(test)]
 mod tests {
-use std::collections::HashMap;
-use crate::htmlnode::HtmlNode;
+use std::path::{Path, PathBuf};
+
+use crate::attributes::Attributes;
+use crate::htmlnode::{HtmlNode, HtmlOptions, NodeKind, OutputFormat};
 use crate::textnode::{TextNode, TextType};
 use crate::utils::*;
 use crate::blocks::*;
+use crate::slug::*;
+use crate::visit::*;
+use crate::select::Selector;
+use crate::parse::*;
+use crate::sanitize::*;
+use crate::links::{page_url, resolve_page_links, LinkPolicy};
+use crate::images::*;
+use crate::responsive::*;
+use crate::highlight::*;
+use crate::math::latex_to_mathml;
+use crate::url::{rebase_urls, BasePath};
+use crate::template::fill_template;
+use crate::check::{check_site, ProblemKind};
+
+    /// A scratch directory, removed when dropped even if the test panics.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("rust-static-site-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
 
     #[test]
     fn test_props_to_html() {
@@ -12,10 +53,11 @@ use crate::blocks::*;
             tag: None,
             value: None,
             children: Vec::new(),
-            props: Some(HashMap::from([
-                ("href".to_string(), "http://www.google.com".to_string()),
-                ("target".to_string(), "_blank".to_string()),
+            props: Some(Attributes::from([
+                ("href", "http://www.google.com"),
+                ("target", "_blank"),
             ])),
+            kind: NodeKind::Text,
         };
 
         let result = ptest.props_to_html();
@@ -152,4 +194,899 @@ the **same** even with inline stuff
         let expected = "<div><pre><code>This is text that _should_ remain\nthe **same** even with inline stuff\n</code></pre></div>";
         assert_eq!(expected, html);
     }
+
+    #[test]
+    fn test_links_after_multibyte_text() {
+        let links = extract_markdown_links("Éowyn — ![shield](s.png) and [Dernhelm](https://rohan.example)");
+        assert_eq!(vec![("Dernhelm", "https://rohan.example")], links);
+    }
+
+    #[test]
+    fn test_unmatched_delimiter_is_literal() {
+        let nodes = text_to_textnodes("a snake_case word and `code`").unwrap();
+        let expect = vec![
+            TextNode{ text: "a snake_case word and ".to_string(), text_type: TextType::Plain, url: None },
+            TextNode{ text: "code".to_string(), text_type: TextType::Code, url: None },
+        ];
+        assert_eq!(expect, nodes);
+
+        let nodes = text_to_textnodes("a_b _c_ d").unwrap();
+        let expect = vec![
+            TextNode{ text: "a_b ".to_string(), text_type: TextType::Plain, url: None },
+            TextNode{ text: "c".to_string(), text_type: TextType::Italic, url: None },
+            TextNode{ text: " d".to_string(), text_type: TextType::Plain, url: None },
+        ];
+        assert_eq!(expect, nodes);
+        let html = markdown_to_html_node("call my_func with _care_, not other_func_name").to_html().unwrap();
+        assert_eq!("<div><p>call my_func with <i>care</i>, not other_func_name</p></div>", html);
+    }
+
+    #[test]
+    fn test_degenerate_markdown_does_not_panic() {
+        for markdown in ["#", "``", "\n\n\n\n", "1. ", "- ", "> ", "```\n```", "[](", "![]()", "ß_ü**"] {
+            let _ = markdown_to_html_node(markdown).to_html();
+        }
+        let many_links = "[a](b) ".repeat(50_000);
+        assert_eq!(100_000, text_to_textnodes(&many_links).unwrap().len());
+    }
+
+    #[test]
+    fn test_slugify_unicode() {
+        assert_eq!("glorfindel-of-gondolin", slugify("Glorfindel of Gondolin!"));
+        assert_eq!("éowyn--dernhelm", slugify("Éowyn & Dernhelm"));
+        assert_eq!("", slugify("?!"));
+    }
+
+    #[test]
+    fn test_heading_ids() {
+        let markdown = "## Section\n\n## Section\n\n## Elsewhere {#section-1}\n\n## _Ages_ of `Arda`";
+        let html = markdown_to_html_node(markdown).to_html().unwrap();
+        let expected = concat!(
+            r#"<div><h2 id="section">Section</h2><h2 id="section-2">Section</h2>"#,
+            r#"<h2 id="section-1">Elsewhere</h2><h2 id="ages-of-arda"><i>Ages</i> of <code>Arda</code></h2></div>"#,
+        );
+        assert_eq!(expected, html);
+
+        // an explicit id is kept even when an earlier heading would generate it
+        let html = markdown_to_html_node("## Section\n\n## Other {#section}").to_html().unwrap();
+        assert_eq!(r#"<div><h2 id="section-1">Section</h2><h2 id="section">Other</h2></div>"#, html);
+        let html = markdown_to_html_node("## Ring\n\n> [!NOTE]\n> ## One {#ring}").to_html().unwrap();
+        assert!(html.starts_with(r#"<div><h2 id="ring-1">Ring</h2>"#) && html.contains(r#"<h2 id="ring">One</h2>"#), "{html}");
+    }
+
+    #[test]
+    fn test_heading_anchor_links() {
+        let options = MarkdownOptions { heading_anchors: true, ..Default::default() };
+        let html = markdown_to_html_node_with_options("# Tom {#tom}", &options).to_html().unwrap();
+        assert_eq!(r##"<div><h1 id="tom">Tom<a class="anchor" href="#tom" aria-hidden="true">#</a></h1></div>"##, html);
+    }
+
+    #[test]
+    fn test_toc_marker() {
+        let markdown = "# Title\n\n[TOC]\n\n## Ages\n\n#### Deep\n\n### Third\n\n## Places";
+        let html = markdown_to_html_node(markdown).to_html().unwrap();
+        let expected = concat!(
+            r##"<div><h1 id="title">Title</h1><ul class="toc"><li><a href="#ages">Ages</a>"##,
+            r##"<ul><li><a href="#deep">Deep</a></li><li><a href="#third">Third</a></li></ul></li>"##,
+            r##"<li><a href="#places">Places</a></li></ul><h2 id="ages">Ages</h2>"##,
+        );
+        assert!(html.starts_with(expected), "{html}");
+    }
+
+    #[test]
+    fn test_toc_levels() {
+        let markdown = "# Title\n\n## Ages\n\n### Third";
+        let options = MarkdownOptions { toc_min_level: 1, toc_max_level: 2, ..Default::default() };
+        let document = markdown_to_document(markdown, &options);
+        let toc = document.toc().unwrap();
+        let expected = r##"<ul class="toc"><li><a href="#title">Title</a><ul><li><a href="#ages">Ages</a></li></ul></li></ul>"##;
+        assert_eq!(expected, toc.to_html().unwrap());
+        let options = MarkdownOptions { toc_min_level: 4, ..Default::default() };
+        assert!(markdown_to_document(markdown, &options).toc().is_none());
+        assert!(!markdown_to_html_node("[TOC]\n\nno headings").to_html().unwrap().contains("toc"));
+    }
+
+    #[test]
+    fn test_definition_list() {
+        let markdown = "Mithril\n: A _silver_ metal\n: Found in **Moria**\n`Balrog`\n: Durin's Bane";
+        assert_eq!(BlockType::DefinitionList, block_to_blocktype(markdown));
+        let html = markdown_to_html_node(markdown).to_html().unwrap();
+        let expected = concat!(
+            "<div><dl><dt>Mithril</dt><dd>A <i>silver</i> metal</dd><dd>Found in <b>Moria</b></dd>",
+            "<dt><code>Balrog</code></dt><dd>Durin's Bane</dd></dl></div>",
+        );
+        assert_eq!(expected, html);
+        assert_eq!(BlockType::Paragraph, block_to_blocktype("Just a line\nand another"));
+    }
+
+    #[test]
+    fn test_github_callout() {
+        let markdown = "> [!WARNING]\n> Do not _touch_ the Ring.\n>\n> Seriously.";
+        assert_eq!(BlockType::Admonition, block_to_blocktype(markdown));
+        let html = markdown_to_html_node(markdown).to_html().unwrap();
+        let expected = concat!(
+            r#"<div><div class="admonition warning"><p class="admonition-title">Warning</p>"#,
+            "<p>Do not <i>touch</i> the Ring.</p><p>Seriously.</p></div></div>",
+        );
+        assert_eq!(expected, html);
+        assert_eq!(BlockType::Quote, block_to_blocktype("> [!RING]\n> not a callout"));
+    }
+
+    #[test]
+    fn test_container_admonition() {
+        let markdown = "Before\n\n:::tip Second breakfast\nEat **early**.\n\nEat often.\n:::\n\nAfter";
+        let blocks = markdown_to_blocks(markdown);
+        assert_eq!(3, blocks.len());
+        let html = markdown_to_html_node(markdown).to_html().unwrap();
+        let expected = concat!(
+            r#"<div><p>Before</p><div class="admonition tip"><p class="admonition-title">Second breakfast</p>"#,
+            "<p>Eat <b>early</b>.</p><p>Eat often.</p></div><p>After</p></div>",
+        );
+        assert_eq!(expected, html);
+    }
+
+    #[test]
+    fn test_text_and_attributes_are_escaped() {
+        let html = markdown_to_html_node("Use `<template>` when a < b && c [q](/?a=\"1\"&b=2)").to_html().unwrap();
+        let expected = r#"<div><p>Use <code>&lt;template&gt;</code> when a &lt; b &amp;&amp; c <a href="/?a=&quot;1&quot;&amp;b=2">q</a></p></div>"#;
+        assert_eq!(expected, html);
+    }
+
+    #[test]
+    fn test_raw_nodes_bypass_escaping() {
+        let raw = HtmlNode::raw_node("<hr>");
+        let script = HtmlNode::leaf_node(Some("script"), "if (a < b) {}", None);
+        let parent = HtmlNode::parent_node("div", vec![raw, script], None);
+        assert_eq!("<div><hr><script>if (a < b) {}</script></div>", parent.to_html().unwrap());
+    }
+
+    #[test]
+    fn test_void_elements() {
+        let html = markdown_to_html_node("![Tom](/images/tom.png)").to_html().unwrap();
+        assert_eq!(r#"<div><figure><img src="/images/tom.png" alt="Tom"><figcaption>Tom</figcaption></figure></div>"#, html);
+
+        let br = HtmlNode::void_node("br", None);
+        let hr = HtmlNode::leaf_node(Some("hr"), "", None);
+        let parent = HtmlNode::parent_node("p", vec![br, hr], None);
+        assert_eq!("<p><br><hr></p>", parent.to_html().unwrap());
+        let xhtml = HtmlOptions { xhtml: true, ..Default::default() };
+        assert_eq!("<p><br /><hr /></p>", parent.to_html_with_options(&xhtml).unwrap());
+
+        let bad = HtmlNode::leaf_node(Some("input"), "text", None);
+        assert!(bad.to_html().is_err());
+    }
+
+    #[test]
+    fn test_parent_props_keep_insertion_order() {
+        let mut props = Attributes::from([("class", "ring"), ("data-id", "1")]);
+        props.insert_bool("hidden");
+        props.insert("class", "one-ring");
+        let child = HtmlNode::leaf_node(Some("span"), "Nazgûl", None);
+        let parent = HtmlNode::parent_node("div", vec![child], Some(props));
+        assert_eq!(r#"<div class="one-ring" data-id="1" hidden><span>Nazgûl</span></div>"#, parent.to_html().unwrap());
+    }
+
+    #[test]
+    fn test_streaming_serialization() {
+        let node = markdown_to_html_node("# Moria\n\nSpeak, _friend_, & enter");
+        let expected = node.to_html().unwrap();
+
+        let mut bytes: Vec<u8> = Vec::new();
+        node.write_html_io(&mut bytes, &HtmlOptions::default()).unwrap();
+        assert_eq!(expected, String::from_utf8(bytes).unwrap());
+        assert_eq!(expected, node.to_string());
+        assert_eq!(expected, format!("{node}"));
+    }
+
+    #[test]
+    fn test_pretty_output() {
+        let node = markdown_to_html_node("# Title\n\n- one _two_\n- three\n\n```\nkeep\n  this\n```");
+        let options = HtmlOptions { format: OutputFormat::Pretty, ..Default::default() };
+        let expected = r#"<div>
+  <h1 id="title">Title</h1>
+  <ul>
+    <li>one <i>two</i></li>
+    <li>three</li>
+  </ul>
+  <pre><code>keep
+  this
+</code></pre>
+</div>"#;
+        assert_eq!(expected, node.to_html_with_options(&options).unwrap());
+    }
+
+    #[test]
+    fn test_minified_output() {
+        let markdown = "A   paragraph\nwith [a  link](/blog/tom) and `two  spaces`\n\n```\nkeep   this\n```";
+        let node = markdown_to_html_node(markdown);
+        let options = HtmlOptions { format: OutputFormat::Minified, ..Default::default() };
+        let expected = "<div><p>A paragraph with <a href=/blog/tom>a link</a> and <code>two  spaces</code></p><pre><code>keep   this\n</code></pre></div>";
+        assert_eq!(expected, node.to_html_with_options(&options).unwrap());
+    }
+
+    #[test]
+    fn test_tree_transformations() {
+        let mut node = markdown_to_html_node("![Tom](/images/tom.png)\n\nSee [Tom](/blog/tom) and `code`\n\n> gone");
+        node.walk_mut(|n| {
+            if n.tag.as_deref() == Some("img") {
+                n.set_attr("loading", "lazy");
+            }
+            if n.tag.as_deref() == Some("a") {
+                n.add_class("internal");
+            }
+        });
+        node.retain(|n| n.tag.as_deref() != Some("blockquote"));
+        node.replace_with(|n| match n.tag.as_deref() {
+            Some("code") => Some(HtmlNode::leaf_node(Some("kbd"), &n.text_content(), None)),
+            _ => None,
+        });
+
+        let expected = concat!(
+            r#"<div><figure><img src="/images/tom.png" alt="Tom" loading="lazy"><figcaption>Tom</figcaption></figure>"#,
+            r#"<p>See <a href="/blog/tom" class="internal">Tom</a> and <kbd>code</kbd></p></div>"#,
+        );
+        assert_eq!(expected, node.to_html().unwrap());
+        assert_eq!(1, node.find_all(|n| n.tag.as_deref() == Some("p")).len());
+    }
+
+    #[test]
+    fn test_visitor() {
+        struct DepthCounter {
+            depth: usize,
+            max_depth: usize,
+        }
+        impl Visitor for DepthCounter {
+            fn enter(&mut self, _node: &HtmlNode) -> bool {
+                self.depth += 1;
+                self.max_depth = self.max_depth.max(self.depth);
+                true
+            }
+            fn leave(&mut self, _node: &HtmlNode) {
+                self.depth -= 1;
+            }
+        }
+
+        let node = markdown_to_html_node("- **deep**");
+        let mut counter = DepthCounter { depth: 0, max_depth: 0 };
+        node.accept(&mut counter);
+        assert_eq!(4, counter.max_depth);
+    }
+
+    #[test]
+    fn test_selector_queries() {
+        let markdown = "# Title\n\n## Uses `code`\n\n[Home](/) and [Wiki](https://lotr.fandom.com) and [Tom](/blog/tom)\n\n- `first` and `second`";
+        let node = markdown_to_html_node(markdown);
+
+        let texts = |selector: &str| -> Vec<String> {
+            node.query(selector).unwrap().iter().map(|n| n.text_content()).collect()
+        };
+        assert_eq!(vec!["Home", "Tom"], texts(r#"a[href^="/"]"#));
+        assert_eq!(vec!["code"], texts("h2 > code"));
+        assert_eq!(vec!["first", "second"], texts("ul code"));
+        assert_eq!(vec!["first"], texts("li > code:first-child"));
+        assert_eq!(vec!["Title", "Uses code"], texts("#title, h2[id|=uses]"));
+        assert_eq!(0, texts("div > code").len());
+        assert!(node.query("a[href").is_err());
+        assert!(node.query("p:hover").is_err());
+    }
+
+    #[test]
+    fn test_selector_mutation() {
+        let mut node = markdown_to_html_node("- one\n- two\n\n[Tom](/blog/tom)");
+        let selector = Selector::parse("div, li").unwrap();
+        assert_eq!(1, node.select_mut(&selector).len());
+
+        let mut count = 0;
+        node.for_each_selected_mut(&selector, |n| {
+            n.add_class("seen");
+            count += 1;
+        });
+        assert_eq!(3, count);
+        for link in node.select_mut(&Selector::parse("a").unwrap()) {
+            link.set_attr("href", "/tom");
+        }
+        assert_eq!(1, node.query(r#"a[href="/tom"]"#).unwrap().len());
+        assert_eq!(3, node.query(".seen").unwrap().len());
+    }
+
+    #[test]
+    fn test_parse_template_round_trip() {
+        let template = std::fs::read_to_string("template.html").unwrap();
+        let document = parse_html_strict(&template).unwrap();
+        let xhtml = HtmlOptions { xhtml: true, ..Default::default() };
+        assert_eq!(template, document.to_html_with_options(&xhtml).unwrap());
+        assert_eq!(2, document.query("head > link[rel=stylesheet]").unwrap().len());
+        assert_eq!("{{ Title }}", document.query("title").unwrap()[0].text_content());
+        assert_eq!("{{ Toc }}", document.query("body > nav").unwrap()[0].text_content());
+    }
+
+    #[test]
+    fn test_parse_tolerates_broken_html() {
+        let html = "<!-- note --><UL><li>One &amp; <b>two<li>Three</ul></span><p>Para<div CLASS=x data-n='1' hidden>&#x2014;&copy;&bogus;</div>";
+        let node = parse_html(html);
+        let expected = concat!(
+            "<!-- note --><ul><li>One &amp; <b>two</b></li><li>Three</li></ul><p>Para</p>",
+            r#"<div class="x" data-n="1" hidden>—©&amp;bogus;</div>"#,
+        );
+        assert_eq!(expected, node.to_html().unwrap());
+        assert!(parse_html_strict(html).is_err());
+        assert!(parse_html_strict("<p>fine<br>and dandy").is_ok());
+
+        let script = parse_html("<script>if (a < b && c) {}</script><textarea>&lt;raw&gt;</textarea>");
+        assert_eq!("<script>if (a < b && c) {}</script><textarea>&lt;raw&gt;</textarea>", script.to_html().unwrap());
+    }
+
+    #[test]
+    fn test_markdown_html_passthrough() {
+        let markdown = "Intro\n\n<div class=\"map\">\n  <img src=\"/images/rivendell.png\" alt=\"Rivendell\">\n</div>\n\n<b>Not</b> a block";
+        assert_eq!(BlockType::Html, block_to_blocktype("<div class=\"map\">\n</div>"));
+        let node = markdown_to_html_node(markdown);
+        assert_eq!(1, node.query("div.map > img").unwrap().len());
+        let html = node.to_html().unwrap();
+        assert!(html.contains(r#"<div class="map">"#), "{html}");
+        assert!(html.ends_with("<p>&lt;b&gt;Not&lt;/b&gt; a block</p></div>"), "{html}");
+    }
+
+    #[test]
+    fn test_builder_matches_manual_construction() {
+        let built = HtmlNode::el("ul")
+            .class("nav")
+            .child(HtmlNode::el("li").child(HtmlNode::el("a").attr("href", "/").text("Home")))
+            .child(HtmlNode::el("li").flag("hidden").text("Secret"));
+        let manual = HtmlNode::parent_node("ul", vec![
+            HtmlNode::parent_node("li", vec![
+                HtmlNode::leaf_node(Some("a"), "Home", Some(Attributes::from([("href", "/")]))),
+            ], None),
+            HtmlNode::leaf_node(Some("li"), "Secret", Some({
+                let mut attrs = Attributes::new();
+                attrs.insert_bool("hidden");
+                attrs
+            })),
+        ], Some(Attributes::from([("class", "nav")])));
+        assert_eq!(manual.to_html().unwrap(), built.to_html().unwrap());
+        assert_eq!("<p></p>", HtmlNode::el("p").to_html().unwrap());
+    }
+
+    #[test]
+    fn test_html_macro() {
+        let items = ["Frodo", "Sam & Merry"];
+        let node = crate::html! {
+            div(id = "party", class = "hobbits", "data-count" = items.len().to_string()) [
+                h2 [ "Members" ],
+                ul [ { HtmlNode::el("li").text(items[0]) }, { HtmlNode::el("li").text(items[1]) } ],
+                hr,
+                p(hidden),
+            ]
+        };
+        assert_eq!(
+            r#"<div id="party" class="hobbits" data-count="2"><h2>Members</h2><ul><li>Frodo</li><li>Sam &amp; Merry</li></ul><hr><p hidden></p></div>"#,
+            node.to_html().unwrap(),
+        );
+        assert_eq!("plain", crate::html!("plain").to_html().unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let text_node = TextNode { text: "Gandalf".to_string(), text_type: TextType::Link, url: Some("/wizards".to_string()) };
+        let json = serde_json::to_string(&text_node).unwrap();
+        assert_eq!(r#"{"text":"Gandalf","text_type":"link","url":"/wizards"}"#, json);
+        assert_eq!(text_node, serde_json::from_str(&json).unwrap());
+        assert_eq!(r#""unordered_list""#, serde_json::to_string(&BlockType::UnorderedList).unwrap());
+
+        let node = HtmlNode::el("p").class("intro").flag("hidden").text("Hi").child(HtmlNode::comment_node(" note "));
+        let json = serde_json::to_string(&node).unwrap();
+        assert_eq!(
+            r#"{"tag":"p","children":[{"value":"Hi"},{"value":" note ","kind":"comment"}],"props":{"class":"intro","hidden":null}}"#,
+            json,
+        );
+        let back: HtmlNode = serde_json::from_str(&json).unwrap();
+        assert_eq!(node.to_html().unwrap(), back.to_html().unwrap());
+
+        let document = markdown_to_document("# Title\n\nBody", &MarkdownOptions::default());
+        let value = serde_json::to_value(&document).unwrap();
+        assert_eq!("title", value["headings"][0]["id"]);
+    }
+
+    #[test]
+    fn test_url_schemes() {
+        assert_eq!(Some("javascript".to_string()), url_scheme(" Java\tScript:alert(1)"));
+        assert_eq!(Some("https".to_string()), url_scheme("HTTPS://example.com"));
+        assert_eq!(None, url_scheme("/blog/tom?a=b:c"));
+        assert_eq!(None, url_scheme("#top"));
+        assert_eq!(None, url_scheme("images/a:b.png"));
+
+        let markdown = "[safe](https://tolkien.org) [mail](mailto:a@b.c) [bad](javascript:void) ![x](data:image/png;base64,AA)";
+        let html = markdown_to_html_node(markdown).to_html().unwrap();
+        assert_eq!(
+            r#"<div><p><a href="https://tolkien.org" rel="noopener noreferrer">safe</a> <a href="mailto:a@b.c">mail</a> <a>bad</a> <img alt="x"></p></div>"#,
+            html,
+        );
+
+        let policy = SanitizePolicy { resource_schemes: vec!["https".to_string(), "data".to_string()], ..Default::default() };
+        let options = MarkdownOptions { sanitize: policy, ..Default::default() };
+        let html = markdown_to_html_node_with_options("![x](data:image/png;base64,AA)", &options).to_html().unwrap();
+        assert!(html.contains(r#"src="data:image/png;base64,AA""#), "{html}");
+    }
+
+    #[test]
+    fn test_strict_sanitizer() {
+        let mut node = HtmlNode::el("div").child(HtmlNode::raw_node(concat!(
+            r#"<p onclick="steal()" class="x">Hi <font color="red">there</font>"#,
+            r#"<script>alert(1)</script><!-- hidden --></p>"#,
+            r#"<img src="/a.png" srcset="/a.png 1x, javascript:x 2x" alt="A">"#,
+            r#"<a href="vbscript:msgbox" title="t">link</a>"#,
+        )));
+        let removed = SanitizePolicy::strict().sanitize(&mut node);
+        assert_eq!(vec!["vbscript:msgbox"], removed);
+        assert_eq!(
+            r#"<div><p class="x">Hi there</p><img src="/a.png" alt="A"><a title="t">link</a></div>"#,
+            node.to_html().unwrap(),
+        );
+
+        let mut trusted = parse_html(r#"<iframe src="https://maps.example"></iframe><a href="JavaScript:x" onclick="y()">z</a>"#);
+        assert_eq!(vec!["JavaScript:x"], SanitizePolicy::default().sanitize(&mut trusted));
+        assert_eq!(
+            r#"<iframe src="https://maps.example"></iframe><a onclick="y()">z</a>"#,
+            trusted.to_html().unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_external_link_policy() {
+        let policy = LinkPolicy {
+            site_url: Some("https://Example.com/blog/".to_string()),
+            new_tab: true,
+            external_class: Some("external".to_string()),
+            ..Default::default()
+        };
+        assert!(policy.is_external("https://tolkien.org"));
+        assert!(policy.is_external("//cdn.example.net/x.js"));
+        assert!(policy.is_external("https://example.com.evil.net/blog/"));
+        assert!(policy.is_external("https://example.com/shop"));
+        assert!(!policy.is_external("http://EXAMPLE.com/blog/tom"));
+        assert!(!policy.is_external("https://example.com/blog?page=2"));
+        assert!(!policy.is_external("/blog/tom"));
+        assert!(!policy.is_external("#top"));
+        assert!(!policy.is_external("mailto:frodo@shire.me"));
+
+        let options = MarkdownOptions { links: policy, ..Default::default() };
+        let markdown = "[Tolkien](https://tolkien.org) and [Tom](https://example.com/blog/tom) and [home](/)";
+        let html = markdown_to_html_node_with_options(markdown, &options).to_html().unwrap();
+        assert_eq!(
+            concat!(
+                r#"<div><p><a href="https://tolkien.org" rel="noopener noreferrer" target="_blank" class="external">Tolkien</a>"#,
+                r#" and <a href="https://example.com/blog/tom">Tom</a> and <a href="/">home</a></p></div>"#,
+            ),
+            html,
+        );
+
+        let mut node = parse_html(r#"<a href="https://tolkien.org" rel="NoOpener author">x</a>"#);
+        LinkPolicy::default().apply(&mut node);
+        assert_eq!(r#"<a href="https://tolkien.org" rel="NoOpener author noreferrer">x</a>"#, node.to_html().unwrap());
+    }
+
+    #[test]
+    fn test_image_dimensions() {
+        let png = std::fs::read("static/images/tolkien.png").unwrap();
+        assert_eq!(Some((1026, 388)), image_dimensions(&png));
+        assert_eq!(Some((3, 2)), image_dimensions(b"GIF89a\x03\x00\x02\x00\x00"));
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46,
+            0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01, 0xE0, 0x02, 0x80, 0x03,
+        ];
+        assert_eq!(Some((640, 480)), image_dimensions(&jpeg));
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+        webp.extend([0x3F, 0x01, 0x00, 0xC7, 0x00, 0x00]);
+        assert_eq!(Some((320, 200)), image_dimensions(&webp));
+        assert_eq!(None, image_dimensions(b"<svg></svg>"));
+
+        // a JPEG whose frame header sits behind more metadata than the first read
+        let dir = TempDir::new("dimensions");
+        let mut big = vec![0xFF, 0xD8];
+        for _ in 0..3 {
+            big.extend([0xFF, 0xE1, 0xFF, 0xFF]);
+            big.extend([0; 0xFFFD]);
+        }
+        big.extend(&jpeg[8..]);
+        std::fs::write(dir.join("big.jpg"), &big).unwrap();
+        let mut node = markdown_to_html_node("![Big](/big.jpg)");
+        let images = ImageOptions { roots: vec![dir.to_path_buf()], lazy: false };
+        assert!(add_image_dimensions(&mut node, Path::new(""), &images).is_empty());
+        assert_eq!(Some("640"), node.query("img").unwrap()[0].get_attr("width"));
+
+        assert_eq!(Some(PathBuf::from("images/a.png")), site_path("../../images/a.png?v=2", Path::new("blog/tom")));
+        assert_eq!(Some(PathBuf::from("blog/tom/a.png")), site_path("./a.png", Path::new("blog/tom")));
+        assert_eq!(None, site_path("https://tolkien.org/a.png", Path::new("")));
+        assert_eq!(None, site_path("../../../a.png", Path::new("blog/tom")));
+
+        let mut node = markdown_to_html_node("![Tolkien](/images/tolkien.png) ![Nope](gone.png)");
+        let missing = add_image_dimensions(&mut node, Path::new("blog"), &ImageOptions::default());
+        assert_eq!(vec!["gone.png".to_string()], missing);
+        assert_eq!(
+            concat!(
+                r#"<div><p><img src="/images/tolkien.png" alt="Tolkien" loading="lazy" decoding="async" width="1026" height="388"> "#,
+                r#"<img src="gone.png" alt="Nope" loading="lazy" decoding="async"></p></div>"#,
+            ),
+            node.to_html().unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_responsive_images() {
+        let dir = TempDir::new("responsive");
+        std::fs::create_dir_all(dir.join("static/images")).unwrap();
+        image::RgbImage::from_pixel(100, 50, image::Rgb([200, 120, 40]))
+            .save(dir.join("static/images/shire.png"))
+            .unwrap();
+
+        let images = ImageOptions { roots: vec![dir.join("static")], lazy: false };
+        let options = ResponsiveOptions {
+            widths: vec![40, 400],
+            output_dir: dir.join("docs"),
+            cache_dir: dir.join("cache"),
+            sizes: "100vw".to_string(),
+            ..Default::default()
+        };
+        let mut node = markdown_to_html_node("![Shire](/images/shire.png)");
+        assert!(add_responsive_images(&mut node, Path::new(""), &images, &options).unwrap().is_empty());
+        assert_eq!(
+            concat!(
+                r#"<div><figure><picture><source type="image/webp" srcset="/images/shire-png-40w.webp 40w, /images/shire-png-100w.webp 100w" sizes="100vw">"#,
+                r#"<img src="/images/shire.png" alt="Shire" srcset="/images/shire-png-40w.png 40w, /images/shire.png 100w" sizes="100vw">"#,
+                "</picture><figcaption>Shire</figcaption></figure></div>",
+            ),
+            node.to_html().unwrap(),
+        );
+        let variant = std::fs::read(dir.join("docs/images/shire-png-40w.png")).unwrap();
+        assert_eq!(Some((40, 20)), image_dimensions(&variant));
+        assert!(dir.join("docs/images/shire-png-100w.webp").is_file());
+        assert_eq!(3, std::fs::read_dir(dir.join("cache")).unwrap().count());
+
+        // a second page reuses the cached encodings
+        std::fs::remove_dir_all(dir.join("docs")).unwrap();
+        let mut again = markdown_to_html_node("![Shire](../images/shire.png)");
+        add_responsive_images(&mut again, Path::new("blog"), &images, &options).unwrap();
+        assert!(dir.join("docs/images/shire-png-40w.webp").is_file());
+        assert_eq!(3, std::fs::read_dir(dir.join("cache")).unwrap().count());
+
+        // a JPEG with the same name gets copies of its own
+        image::RgbImage::from_pixel(80, 40, image::Rgb([20, 60, 160]))
+            .save(dir.join("static/images/shire.jpg"))
+            .unwrap();
+        let mut jpeg = markdown_to_html_node("![Shire](/images/shire.jpg)");
+        add_responsive_images(&mut jpeg, Path::new(""), &images, &options).unwrap();
+        assert!(jpeg.to_html().unwrap().contains("/images/shire-jpg-40w.webp 40w"));
+        let png_copy = image::open(dir.join("docs/images/shire-png-40w.webp")).unwrap();
+        let jpeg_copy = image::open(dir.join("docs/images/shire-jpg-40w.webp")).unwrap();
+        assert_ne!(png_copy.to_rgb8().get_pixel(0, 0), jpeg_copy.to_rgb8().get_pixel(0, 0));
+
+        // a truncated image is reported and left alone, as is a hand-written picture
+        let png = std::fs::read(dir.join("static/images/shire.png")).unwrap();
+        std::fs::write(dir.join("static/images/broken.png"), &png[..png.len() / 2]).unwrap();
+        let markdown = "![Broken](/images/broken.png)\n\n<figure><picture><img src=\"/images/shire.png\"></picture></figure>";
+        let mut node = markdown_to_html_node(markdown);
+        let untouched = node.clone();
+        let failed = add_responsive_images(&mut node, Path::new(""), &images, &options).unwrap();
+        assert_eq!(vec!["/images/broken.png".to_string()], failed);
+        assert_eq!(untouched.to_html().unwrap(), node.to_html().unwrap());
+        assert_eq!(1, node.query("picture > img").unwrap().len());
+    }
+
+    #[test]
+    fn test_image_figures() {
+        assert_eq!((r#"/a.png"#, Some("The Shire")), split_link_title(r#"/a.png "The Shire""#));
+        assert_eq!(("/a.png", Some("Bag \"End\"")), split_link_title(r#" /a.png 'Bag "End"' "#));
+        assert_eq!(("/a.png", None), split_link_title("/a.png"));
+
+        let markdown = "![Tolkien](/images/tolkien.png \"J.R.R. Tolkien, 1955\")\n\n![](/images/tom.png)\n\nSee ![Tom](/images/tom.png)";
+        let html = markdown_to_html_node(markdown).to_html().unwrap();
+        assert_eq!(
+            concat!(
+                r#"<div><figure><img src="/images/tolkien.png" alt="Tolkien" title="J.R.R. Tolkien, 1955">"#,
+                r#"<figcaption>J.R.R. Tolkien, 1955</figcaption></figure>"#,
+                r#"<figure><img src="/images/tom.png" alt=""></figure>"#,
+                r#"<p>See <img src="/images/tom.png" alt="Tom"></p></div>"#,
+            ),
+            html,
+        );
+
+        let options = MarkdownOptions { figures: false, ..Default::default() };
+        let html = markdown_to_html_node_with_options("![Tom](/images/tom.png)", &options).to_html().unwrap();
+        assert_eq!(r#"<div><p><img src="/images/tom.png" alt="Tom"></p></div>"#, html);
+    }
+
+    #[test]
+    fn test_syntax_highlighting() {
+        let markdown = "```rust\nfn main() {\n    let s = \"<hi>\"; // greet\n}\n```";
+        let html = markdown_to_html_node(markdown).to_html().unwrap();
+        assert_eq!(
+            concat!(
+                r#"<div><pre><code class="language-rust"><span class="hl-keyword">fn</span> <span class="hl-function">main</span>() {"#,
+                "\n    ",
+                r#"<span class="hl-keyword">let</span> s = <span class="hl-string">"&lt;hi&gt;"</span>; <span class="hl-comment">// greet</span>"#,
+                "\n}\n</code></pre></div>",
+            ),
+            html,
+        );
+
+        let options = MarkdownOptions {
+            highlight: Some(HighlightOptions { mode: HighlightMode::InlineStyles, ..Default::default() }),
+            ..Default::default()
+        };
+        let html = markdown_to_html_node_with_options("```json\n{\"ring\": true}\n```", &options).to_html().unwrap();
+        assert!(html.contains(r#"<span style="color: #d3869b">"ring"</span>: <span style="color: #e9b872">true</span>"#), "{html}");
+
+        // unknown languages keep their class but stay plain
+        let html = markdown_to_html_node("```elvish\nmae govannen\n```").to_html().unwrap();
+        assert_eq!("<div><pre><code class=\"language-elvish\">mae govannen\n</code></pre></div>", html);
+
+        let tokens = tokenize("<a href='/x'>'a</a>", "html").unwrap();
+        assert_eq!(
+            vec![
+                (None, "<"), (Some(TokenKind::Tag), "a"), (None, " "), (Some(TokenKind::Attribute), "href"), (None, "="),
+                (Some(TokenKind::String), "'/x'"), (None, ">'a</"), (Some(TokenKind::Tag), "a"), (None, ">"),
+            ],
+            tokens,
+        );
+        assert_eq!(Some(TokenKind::Type), tokenize("&'a str", "rust").unwrap()[1].0);
+        assert!(Theme::default().css().contains(".hl-keyword { color: #dda15e; font-weight: bold; }"));
+    }
+
+    #[test]
+    fn test_math() {
+        let html = markdown_to_html_node("Age $a_1 = \\frac{n^2}{2}$ costs $5 and $10, not `$x$`").to_html().unwrap();
+        assert_eq!(
+            concat!(
+                r#"<div><p>Age <math display="inline"><mrow><msub><mi>a</mi><mn>1</mn></msub><mo>=</mo>"#,
+                "<mfrac><msup><mi>n</mi><mn>2</mn></msup><mn>2</mn></mfrac></mrow></math>",
+                " costs $5 and $10, not <code>$x$</code></p></div>",
+            ),
+            html,
+        );
+
+        assert_eq!(BlockType::Math, block_to_blocktype("$$\n\\sum_{i=1}^n i\n$$"));
+        assert_eq!(BlockType::Paragraph, block_to_blocktype("$$a$$ and $$b$$"));
+        let html = markdown_to_html_node("$$\n\\sum_{i=1}^n i\n$$").to_html().unwrap();
+        assert_eq!(
+            concat!(
+                r#"<div><math display="block"><mrow><munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover>"#,
+                "<mi>i</mi></mrow></math></div>",
+            ),
+            html,
+        );
+
+        let node = latex_to_mathml(r"\sqrt[3]{x} \left( \alpha' \right) \text{if} \mathbf{v} \nope", false);
+        assert_eq!(
+            concat!(
+                r#"<math display="inline"><mrow><mroot><mi>x</mi><mn>3</mn></mroot><mrow><mo>(</mo><msup><mi>α</mi><mo>′</mo></msup><mo>)</mo></mrow>"#,
+                r#"<mtext>if</mtext><mi mathvariant="bold">v</mi><merror><mtext>\nope</mtext></merror></mrow></math>"#,
+            ),
+            node.to_html().unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_empty_document() {
+        assert_eq!("<div></div>", markdown_to_html_node("").to_html().unwrap());
+        assert_eq!("<div></div>", markdown_to_html_node("\n\n").to_html().unwrap());
+    }
+
+    #[test]
+    fn test_base_path_rebasing() {
+        assert_eq!("/site/docs/", BasePath::parse("site\\docs").unwrap().as_str());
+        assert_eq!("/", BasePath::parse("").unwrap().as_str());
+        assert!(BasePath::parse("https://example.com/site/").is_err());
+
+        let template = concat!(
+            "<html><head><title>{{ Title }}</title><link href=\"/index.css\" rel=\"stylesheet\">",
+            "<style>body { background: url('/bg.png') }</style></head>",
+            "<body><article>{{ Content }}</article></body></html>",
+        );
+        let mut page = parse_html(template);
+        let content = markdown_to_html_node("[Home](/) ![Logo](/logo.png)\n\n```html\n<a href=\"/\">x</a>\n```");
+        fill_template(&mut page, vec![
+            ("{{ Title }}", HtmlNode::text_node("Fish & Chips")),
+            ("{{ Content }}", content),
+        ]);
+        rebase_urls(&mut page, &"/site".parse().unwrap());
+        let html = page.to_html().unwrap();
+        assert!(html.starts_with(concat!(
+            "<html><head><title>Fish &amp; Chips</title><link href=\"/site/index.css\" rel=\"stylesheet\">",
+            "<style>body { background: url('/site/bg.png') }</style></head>",
+            "<body><article><div><p><a href=\"/site/\">Home</a> <img src=\"/site/logo.png\" alt=\"Logo\"",
+        )), "{html}");
+        assert!(html.contains(r#"<span class="hl-string">"/"</span>"#), "{html}");
+    }
+
+    #[test]
+    fn test_markdown_page_links() {
+        assert_eq!("/", page_url(Path::new("index.md")));
+        assert_eq!("/blog/tom/", page_url(Path::new("blog/tom/index.md")));
+        assert_eq!("/blog/notes.html", page_url(Path::new("blog/notes.md")));
+
+        let dir = TempDir::new("page-links");
+        std::fs::create_dir_all(dir.join("blog/tom")).unwrap();
+        std::fs::create_dir_all(dir.join("blog/majesty")).unwrap();
+        std::fs::write(dir.join("blog/tom/index.md"), "# Tom").unwrap();
+
+        let mut node = markdown_to_html_node(
+            "[Tom](../tom/index.md#mistake) [Also](/blog/tom/index.md) [Readme](https://example.com/README.md) [Notes](notes.txt)",
+        );
+        resolve_page_links(&mut node, Path::new("blog/majesty"), &dir).unwrap();
+        assert_eq!(
+            concat!(
+                r#"<div><p><a href="/blog/tom/#mistake">Tom</a> <a href="/blog/tom/">Also</a> "#,
+                r#"<a href="https://example.com/README.md" rel="noopener noreferrer">Readme</a> <a href="notes.txt">Notes</a></p></div>"#,
+            ),
+            node.to_html().unwrap(),
+        );
+
+        let mut node = markdown_to_html_node("[Gone](../gone.md)");
+        assert!(resolve_page_links(&mut node, Path::new("blog/majesty"), &dir).is_err());
+        // `..` can't climb out of the content directory onto a real file
+        std::fs::write(dir.join("x.md"), "# X").unwrap();
+        let mut node = markdown_to_html_node("[X](../../../x.md)");
+        assert!(resolve_page_links(&mut node, Path::new(""), &dir).is_err());
+        let mut node = markdown_to_html_node("[X](../x.md)");
+        resolve_page_links(&mut node, Path::new("blog"), &dir).unwrap();
+        assert_eq!(r#"<div><p><a href="/x.html">X</a></p></div>"#, node.to_html().unwrap());
+    }
+
+    #[test]
+    fn test_site_check() {
+        let dir = TempDir::new("check");
+        let (docs, content) = (dir.join("docs"), dir.join("content"));
+        std::fs::create_dir_all(docs.join("blog/tom")).unwrap();
+        std::fs::create_dir_all(docs.join("blog/lost")).unwrap();
+        std::fs::create_dir_all(content.join("blog/tom")).unwrap();
+        std::fs::write(docs.join("index.css"), "").unwrap();
+        std::fs::write(content.join("blog/tom/index.md"), "# Tom\n\n[Home](/)\n\n[Gone](/blog/gone) and ![Missing](/missing.png)\n").unwrap();
+        std::fs::write(docs.join("index.html"), concat!(
+            r#"<link href="/site/index.css" rel="stylesheet"><h1 id="top-hat">Home</h1><h2 id="über">Über</h2>"#,
+            r##"<a href="#%C3%BCber">Über</a> <a href="#über">Über</a> <a href="#%zz">Bad escape</a> "##,
+            r#"<a href="/site/blog/tom#tom">Tom</a> <a href="blog/tom/index.html#nope">Tom again</a> <a href="https://example.com/x">x</a>"#,
+        )).unwrap();
+        std::fs::write(docs.join("blog/tom/index.html"), concat!(
+            r#"<h1 id="tom">Tom</h1><a href="/site/">Home</a> <a href="/site/blog/gone">Gone</a> "#,
+            r#"<img src="/site/missing.png" srcset="/site/missing.png 1x" alt="Missing">"#,
+        )).unwrap();
+        std::fs::write(docs.join("blog/lost/index.html"), r##"<a href="#top">Top</a>"##).unwrap();
+
+        let problems = check_site(&docs, &content, &"/site/".parse().unwrap()).unwrap();
+        let found: Vec<(ProblemKind, &str, Option<usize>)> = problems.iter()
+            .map(|problem| (problem.kind, problem.url.as_str(), problem.line))
+            .collect();
+        assert_eq!(vec![
+            (ProblemKind::BrokenLink, "/site/blog/gone", Some(5)),
+            (ProblemKind::MissingAsset, "/site/missing.png", Some(5)),
+            (ProblemKind::MissingAnchor, "#%zz", None),
+            (ProblemKind::MissingAnchor, "blog/tom/index.html#nope", None),
+            (ProblemKind::OrphanPage, "", None),
+        ], found);
+        assert_eq!(
+            format!("{}:5: broken link \"/site/blog/gone\" in blog/tom/index.html", content.join("blog/tom/index.md").display()),
+            problems[0].to_string(),
+        );
+        assert_eq!(Path::new("blog/lost/index.html"), problems[4].page);
+    }
+
+    #[test]
+    fn test_deeply_nested_math() {
+        for tex in ["{".repeat(10_000) + "x", r"\frac".repeat(10_000), r"\left(".repeat(10_000), "{^".repeat(10_000)] {
+            let html = latex_to_mathml(&tex, false).to_html().unwrap();
+            assert!(html.contains("<merror>"), "{}", &html[..200]);
+        }
+        let html = markdown_to_html_node(&format!("${}x$", "{".repeat(10_000))).to_html().unwrap();
+        assert!(html.contains("<merror>"));
+    }
+
+    #[test]
+    fn test_many_unmatched_dollars() {
+        let text = "$a ".repeat(50_000);
+        let nodes = text_to_textnodes(&text).unwrap();
+        assert_eq!(vec![TextNode { text: text.clone(), text_type: TextType::Plain, url: None }], nodes);
+
+        let nodes = text_to_textnodes(&"$a$ ".repeat(50_000)).unwrap();
+        assert_eq!(100_000, nodes.len());
+    }
+
+    #[test]
+    fn test_deeply_nested_html() {
+        let input = format!("{}x{}", "<div>".repeat(10_000), "</div>".repeat(10_000));
+        let node = parse_html(&input);
+        let html = node.to_html().unwrap();
+        assert!(html.starts_with(&"<div>".repeat(256)));
+        assert!(html.contains("<div></div>x"));
+        assert_eq!(10_000, node.find_all(|node| node.tag.as_deref() == Some("div")).len());
+        assert!(parse_html_strict(&input).is_err());
+    }
+
+    #[test]
+    fn test_colon_line_in_prose() {
+        let markdown = "The Ring bore an inscription\nin the Black Speech of Mordor\n: one Ring to rule them all";
+        assert_eq!(BlockType::Paragraph, block_to_blocktype(markdown));
+        let markdown = "Mithril\n: A silver metal\nFound in Moria\nand nowhere else\n: Durin's Bane";
+        assert_eq!(BlockType::Paragraph, block_to_blocktype(markdown));
+        assert_eq!(BlockType::Paragraph, block_to_blocktype(": no term\n: at all"));
+    }
+
+    #[test]
+    fn test_pretty_output_uppercase_tags() {
+        let paragraph = |text| HtmlNode::leaf_node(Some("P"), text, None);
+        let node = HtmlNode::parent_node("DIV", vec![paragraph("One"), paragraph("Two")], None);
+        let options = HtmlOptions { format: OutputFormat::Pretty, ..Default::default() };
+        assert_eq!("<DIV>\n  <P>One</P>\n  <P>Two</P>\n</DIV>", node.to_html_with_options(&options).unwrap());
+    }
+
+    #[test]
+    fn test_unclosed_container() {
+        let markdown = ":::note\nHello\n\n# Later heading\n\n- a list\n\n```rust\nfn x(){}\n```";
+        let blocks = markdown_to_blocks(markdown);
+        assert_eq!(4, blocks.len());
+        assert_eq!(BlockType::Paragraph, block_to_blocktype(&blocks[0]));
+        let html = markdown_to_html_node_with_options(markdown, &MarkdownOptions { highlight: None, ..Default::default() })
+            .to_html()
+            .unwrap();
+        let expected = concat!(
+            r#"<div><p>:::note Hello</p><h1 id="later-heading">Later heading</h1><ul><li>a list</li></ul>"#,
+            "<pre><code class=\"language-rust\">fn x(){}\n</code></pre></div>",
+        );
+        assert_eq!(expected, html);
+
+        // an unclosed opener doesn't steal the fence of a later container
+        let blocks = markdown_to_blocks(":::note\nHello\n\n:::tip\nEat.\n:::");
+        assert_eq!(vec![":::note\nHello", ":::tip\nEat.\n:::"], blocks);
+    }
+
+    #[test]
+    fn test_admonition_body_blocks() {
+        let html = markdown_to_html_node("> [!NOTE]\n> - a\n> - b").to_html().unwrap();
+        let expected = concat!(
+            r#"<div><div class="admonition note"><p class="admonition-title">Note</p>"#,
+            "<ul><li>a</li><li>b</li></ul></div></div>",
+        );
+        assert_eq!(expected, html);
+
+        let markdown = "## Road\n\n:::warning\n## Road\n\n1. Bree\n2. Rivendell\n\n```\nkeep\n```\n:::";
+        let html = markdown_to_html_node(markdown).to_html().unwrap();
+        let expected = concat!(
+            r#"<div><h2 id="road">Road</h2><div class="admonition warning"><p class="admonition-title">Warning</p>"#,
+            r#"<h2 id="road-1">Road</h2><ol><li>Bree</li><li>Rivendell</li></ol><pre><code>keep"#,
+            "\n</code></pre></div></div>",
+        );
+        assert_eq!(expected, html);
+    }
+
+    #[test]
+    fn test_many_ampersands() {
+        let text = "&".repeat(100_000) + ";";
+        assert_eq!(text, decode_entities(&text));
+        let html = parse_html(&format!("<p>{}&amp;</p>", "a & ".repeat(50_000))).to_html().unwrap();
+        assert!(html.ends_with("a &amp; &amp;</p>"), "{}", &html[html.len() - 40..]);
+    }
+
+    #[test]
+    fn test_retain_removes_every_child() {
+        let mut node = parse_html("<div><p><img src=a></p></div>");
+        node.retain(|n| n.tag.as_deref() != Some("img"));
+        assert_eq!("<div><p></p></div>", node.to_html().unwrap());
+
+        let mut node = markdown_to_html_node("![Tom](/images/tom.png)\n\nText");
+        node.retain(|n| n.tag.as_deref() != Some("figure") && n.tag.as_deref() != Some("p"));
+        assert_eq!("<div></div>", node.to_html().unwrap());
+    }
+
+    #[test]
+    fn test_template_slots_used_more_than_once() {
+        let mut page = parse_html(r#"<head><title>{{ Title }}</title><meta content="{{ Title }}"></head><h1>{{ Title }}</h1>{{ Content }}"#);
+        fill_template(&mut page, vec![
+            ("{{ Title }}", HtmlNode::el("b").text("Tom")),
+            ("{{ Content }}", markdown_to_html_node("Old _Tom_")),
+        ]);
+        assert_eq!(
+            r#"<head><title><b>Tom</b></title><meta content="Tom"></head><h1><b>Tom</b></h1><div><p>Old <i>Tom</i></p></div>"#,
+            page.to_html().unwrap(),
+        );
+    }
 }
\ No newline at end of file
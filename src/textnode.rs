@@ -1,7 +1,11 @@
 use crate::htmlnode::HtmlNode;
-use std::collections::HashMap;
+use crate::attributes::Attributes;
+use crate::math::latex_to_mathml;
+use crate::utils::split_link_title;
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum TextType {
     Plain,
     Bold,
@@ -9,9 +13,14 @@ pub enum TextType {
     Code,
     Link,
     Image,
+    /// A `$formula$`, in TeX.
+    Math,
+    /// A `$$formula$$`, in TeX.
+    DisplayMath,
 }
 
 #[derive(PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TextNode {
     pub text: String,
     pub text_type: TextType,
@@ -25,16 +34,25 @@ impl From<TextNode> for HtmlNode {
             TextType::Bold => HtmlNode::leaf_node(Some("b"), &tnode.text, None),
             TextType::Italic => HtmlNode::leaf_node(Some("i"), &tnode.text, None),
             TextType::Code => HtmlNode::leaf_node(Some("code"), &tnode.text, None),
+            TextType::Math => latex_to_mathml(&tnode.text, false),
+            TextType::DisplayMath => latex_to_mathml(&tnode.text, true),
             TextType::Link => {
-                let mut props = HashMap::new();
-                props.insert("href".to_string(), tnode.url.clone().unwrap());
+                let destination = tnode.url.unwrap_or_default();
+                let (url, title) = split_link_title(&destination);
+                let mut props = Attributes::from([("href", url)]);
+                if let Some(title) = title {
+                    props.insert("title", title);
+                }
                 HtmlNode::leaf_node(Some("a"), &tnode.text, Some(props))
             },
             TextType::Image => {
-                let mut props = HashMap::new();
-                props.insert("src".to_string(), tnode.url.clone().unwrap());
-                props.insert("alt".to_string(), tnode.text.clone());
-                HtmlNode::leaf_node(Some("img"), "", Some(props))
+                let destination = tnode.url.unwrap_or_default();
+                let (url, title) = split_link_title(&destination);
+                let mut props = Attributes::from([("src", url), ("alt", &tnode.text)]);
+                if let Some(title) = title {
+                    props.insert("title", title);
+                }
+                HtmlNode::void_node("img", Some(props))
             },
         }
     }
@@ -0,0 +1,58 @@
+use crate::attributes::Attributes;
+use crate::htmlnode::HtmlNode;
+
+/// A heading as it was rendered on the page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heading {
+    pub level: u8,
+    pub id: String,
+    pub text: String,
+}
+
+/// One entry of a table of contents, with the headings nested beneath it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocEntry {
+    pub heading: Heading,
+    pub children: Vec<TocEntry>,
+}
+
+/// Nests the headings between `min_level` and `max_level` (inclusive) into an
+/// outline. A heading that skips levels (an h4 straight after an h2) is
+/// nested directly under the previous shallower heading.
+pub fn build_toc(headings: &[Heading], min_level: u8, max_level: u8) -> Vec<TocEntry> {
+    let mut roots = Vec::new();
+    for heading in headings.iter().filter(|h| (min_level..=max_level).contains(&h.level)) {
+        insert_entry(&mut roots, heading.clone());
+    }
+    roots
+}
+
+fn insert_entry(list: &mut Vec<TocEntry>, heading: Heading) {
+    match list.last_mut() {
+        Some(last) if heading.level > last.heading.level => insert_entry(&mut last.children, heading),
+        _ => list.push(TocEntry { heading, children: Vec::new() }),
+    }
+}
+
+/// Renders an outline as nested `ul` lists of links to the heading ids.
+/// Returns `None` for an empty outline, since an empty `ul` has nothing to render.
+pub fn toc_to_html_node(entries: &[TocEntry]) -> Option<HtmlNode> {
+    let props = Attributes::from([("class", "toc")]);
+    entries_to_ul(entries, Some(props))
+}
+
+fn entries_to_ul(entries: &[TocEntry], props: Option<Attributes>) -> Option<HtmlNode> {
+    if entries.is_empty() {
+        return None;
+    }
+    let items = entries.iter()
+        .map(|entry| {
+            let href = Attributes::from([("href", format!("#{}", entry.heading.id))]);
+            let mut children = vec![HtmlNode::leaf_node(Some("a"), &entry.heading.text, Some(href))];
+            children.extend(entries_to_ul(&entry.children, None));
+            HtmlNode::parent_node("li", children, None)
+        })
+        .collect();
+    Some(HtmlNode::parent_node("ul", items, props))
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::errors::NodeError;
+use crate::htmlnode::HtmlNode;
+
+static CSS_URL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"url\(\s*(['"]?)([^'")\s]*)(['"]?)\s*\)"#).expect("css url regex is valid")
+});
+
+/// Attributes holding a single URL.
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "poster", "action", "formaction", "cite", "data"];
+
+/// The path a site is served under, such as `/` or `/rust-static-site/`.
+/// Always starts and ends with `/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasePath(String);
+
+impl Default for BasePath {
+    fn default() -> Self {
+        BasePath("/".to_string())
+    }
+}
+
+impl BasePath {
+    /// Parses a base path, adding the leading and trailing `/` if they're
+    /// missing. Backslashes, as in a Windows path, are read as `/`. A full
+    /// URL, a query or a fragment is an error.
+    pub fn parse(path: &str) -> Result<Self, NodeError> {
+        let path = path.trim().replace('\\', "/");
+        if path.contains("://") || path.starts_with("//") || path.contains(['?', '#']) || path.contains(char::is_whitespace) {
+            return Err(NodeError::ValueError(format!("not a base path: {path:?}")));
+        }
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        if segments.is_empty() {
+            return Ok(BasePath::default());
+        }
+        Ok(BasePath(format!("/{}/", segments.join("/"))))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// `url` moved under the base path if it is root-relative (`/about`), or
+    /// `None` for URLs that are relative, on another site or already fine.
+    pub fn rebase(&self, url: &str) -> Option<String> {
+        if self.0 == "/" {
+            return None;
+        }
+        let rest = url.strip_prefix('/').filter(|rest| !rest.starts_with('/'))?;
+        Some(format!("{}{rest}", self.0))
+    }
+
+    /// Rebases each URL in a `srcset` such as `/a.png 1x, /b.png 2x`.
+    pub fn rebase_srcset(&self, srcset: &str) -> Option<String> {
+        let mut changed = false;
+        let candidates: Vec<String> = srcset.split(',')
+            .map(|candidate| {
+                let candidate = candidate.trim();
+                let (url, descriptor) = candidate.split_once(char::is_whitespace).unwrap_or((candidate, ""));
+                match self.rebase(url) {
+                    Some(url) => {
+                        changed = true;
+                        [url.as_str(), descriptor.trim()].join(" ").trim_end().to_string()
+                    },
+                    None => candidate.to_string(),
+                }
+            })
+            .collect();
+        changed.then(|| candidates.join(", "))
+    }
+
+    /// Rebases the `url(...)` references in a stylesheet or `style` attribute.
+    pub fn rebase_css(&self, css: &str) -> Option<String> {
+        let mut changed = false;
+        let rebased = CSS_URL_RE.replace_all(css, |cap: &regex::Captures| match self.rebase(&cap[2]) {
+            Some(url) => {
+                changed = true;
+                format!("url({}{url}{})", &cap[1], &cap[3])
+            },
+            None => cap[0].to_string(),
+        });
+        changed.then(|| rebased.into_owned())
+    }
+}
+
+impl FromStr for BasePath {
+    type Err = NodeError;
+
+    fn from_str(path: &str) -> Result<Self, NodeError> {
+        BasePath::parse(path)
+    }
+}
+
+impl fmt::Display for BasePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Moves every root-relative URL in the tree under `base`: link and media
+/// attributes, `srcset`, and `url(...)` in `style` attributes and `<style>`
+/// elements. Text, including code samples, is never touched.
+pub fn rebase_urls(node: &mut HtmlNode, base: &BasePath) {
+    if base.as_str() == "/" {
+        return;
+    }
+    node.walk_mut(|node| {
+        if node.tag.is_none() {
+            return;
+        }
+        for key in URL_ATTRIBUTES {
+            if let Some(url) = node.get_attr(key).and_then(|url| base.rebase(url)) {
+                node.set_attr(key, &url);
+            }
+        }
+        for key in ["srcset", "imagesrcset"] {
+            if let Some(srcset) = node.get_attr(key).and_then(|srcset| base.rebase_srcset(srcset)) {
+                node.set_attr(key, &srcset);
+            }
+        }
+        if let Some(style) = node.get_attr("style").and_then(|style| base.rebase_css(style)) {
+            node.set_attr("style", &style);
+        }
+        if node.tag.as_deref() == Some("style") {
+            for child in node.children.iter_mut() {
+                if let Some(css) = child.value.as_deref().and_then(|css| base.rebase_css(css)) {
+                    child.value = Some(css);
+                }
+            }
+        }
+    });
+}
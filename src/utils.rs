@@ -1,11 +1,34 @@
 use crate::errors::NodeError;
 use crate::textnode::{TextNode, TextType};
 use regex::Regex;
+use std::sync::LazyLock;
+
+static IMAGE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"!\[([^\[\]]*)\]\(([^\(\)]*)\)").expect("image regex is valid")
+});
+
+static LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[([^\[\]]*)\]\(([^\(\)]*)\)").expect("link regex is valid")
+});
+
+static TITLE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^\s*(\S*)\s+(?:"([^"]*)"|'([^']*)')\s*$"#).expect("title regex is valid")
+});
+
+/// Splits a link or image destination such as `/a.png "A title"` into the
+/// URL and its title, if it has one.
+pub fn split_link_title(destination: &str) -> (&str, Option<&str>) {
+    match TITLE_RE.captures(destination) {
+        Some(cap) => {
+            let url = cap.get(1).map_or("", |m| m.as_str());
+            (url, cap.get(2).or(cap.get(3)).map(|m| m.as_str()))
+        },
+        None => (destination.trim(), None),
+    }
+}
 
 pub fn extract_markdown_images(text: &str) -> Vec<(&str, &str)> {
-    let re = Regex::new(r"!\[([^\[\]]*)\]\(([^\(\)]*)\)").unwrap();
-    
-    re.captures_iter(text)
+    IMAGE_RE.captures_iter(text)
         .map(|cap| {
             let (_, [alt_text, url]) = cap.extract();
             (alt_text, url)
@@ -14,12 +37,11 @@ pub fn extract_markdown_images(text: &str) -> Vec<(&str, &str)> {
 }
 
 pub fn extract_markdown_links(text: &str) -> Vec<(&str, &str)> {
-    let re = Regex::new(r"\[([^\[\]]*)\]\(([^\(\)]*)\)").unwrap();
-    
-    re.captures_iter(text)
+    LINK_RE.captures_iter(text)
         .filter(|cap| {
-            let start_pos = cap.get(0).unwrap().start();
-            start_pos == 0 || text.chars().nth(start_pos - 1) != Some('!')
+            // match offsets are byte offsets, so look back in bytes rather than chars
+            let start_pos = cap.get(0).map_or(0, |m| m.start());
+            !text[..start_pos].ends_with('!')
         })
         .map(|cap| {
             let (_, [link_text, url]) = cap.extract();
@@ -32,7 +54,7 @@ pub fn split_nodes_delimeter(old_nodes: Vec<TextNode>, delimeter: &str, text_typ
     let mut new_nodes = Vec::new();
 
     for node in old_nodes {
-        if &node.text_type != &TextType::Plain {
+        if node.text_type != TextType::Plain {
             new_nodes.push(node);
             continue;
         }
@@ -41,16 +63,21 @@ pub fn split_nodes_delimeter(old_nodes: Vec<TextNode>, delimeter: &str, text_typ
             continue;
         }
 
-        let parts: Vec<&str> = node.text.split(delimeter).collect();
-        if parts.len() %2 == 0 {
-            return Err(NodeError::ParseError(node.text.clone().to_string()));
+        let mut parts: Vec<String> = node.text.split(delimeter).map(str::to_string).collect();
+        if parts.len().is_multiple_of(2) {
+            // an unmatched delimiter is literal text, e.g. snake_case or a lone `
+            let last = parts.pop().unwrap_or_default();
+            if let Some(unmatched) = parts.last_mut() {
+                unmatched.push_str(delimeter);
+                unmatched.push_str(&last);
+            }
         }
 
         for (i, part) in parts.iter().enumerate() {
             if i % 2 == 0 {
                 if !part.is_empty() {
                     new_nodes.push( TextNode {
-                        text: part.to_string(),
+                        text: part.clone(),
                         text_type: TextType::Plain,
                         url: None,
                     });
@@ -58,7 +85,7 @@ pub fn split_nodes_delimeter(old_nodes: Vec<TextNode>, delimeter: &str, text_typ
             } else {
                 if !part.is_empty() {
                     new_nodes.push( TextNode {
-                        text: part.to_string(),
+                        text: part.clone(),
                         text_type,
                         url: None,
                     });
@@ -71,138 +98,137 @@ pub fn split_nodes_delimeter(old_nodes: Vec<TextNode>, delimeter: &str, text_typ
     Ok(new_nodes)
 }
 
-pub fn split_nodes_link(old_nodes: Vec<TextNode>) -> Result<Vec<TextNode>, NodeError> {
-    let mut new_nodes = Vec::new();
+/// Locates the first markdown link in `text`, returning its byte span along
+/// with the link text and url.
+fn find_markdown_link(text: &str) -> Option<(usize, usize, &str, Option<&str>)> {
+    LINK_RE.captures_iter(text)
+        .filter_map(|cap| {
+            let whole = cap.get(0)?;
+            if text[..whole.start()].ends_with('!') {
+                return None;
+            }
+            Some((whole.start(), whole.end(), cap.get(1)?.as_str(), Some(cap.get(2)?.as_str())))
+        })
+        .next()
+}
 
-    for node in old_nodes {
-        if node.text.is_empty() {
-            continue;
-        }
-        if node.text_type != TextType::Plain {
-            new_nodes.push(node);
-            continue;
-        }
-        let links = extract_markdown_links(&node.text);
-        //println!("{:?}", links);
-        if links.is_empty() {
-            new_nodes.push(node);
-            continue;
-        }
-        let (title, url) = links.first()
-            .ok_or_else(|| NodeError::ParseError("No links found".to_string()))?;
-        let pat = format!("[{}]({})", title, url);
-        //println!("Hunting for pattern: {}", pat);
-        let sections: Vec<_> = node.text.splitn(2, &pat).collect();
-        //println!("Found sections: {:?}", sections);
-
-        match sections.as_slice() {
-            [] => {
-                return Err(NodeError::ParseError("Split returned empty result".to_string()));
-            },
-            [text] => {
-                new_nodes.push( TextNode {
-                    text: text.to_string(),
-                    text_type: TextType::Plain,
-                    url: None,
-                });
-            },
-            [before, after] => {
-                if !before.is_empty() {
-                    new_nodes.push( TextNode {
-                        text: before.to_string(),
-                        text_type: TextType::Plain,
-                        url: None,
-                    });
-                }
+/// Locates the first markdown image in `text`, returning its byte span along
+/// with the alt text and url.
+fn find_markdown_image(text: &str) -> Option<(usize, usize, &str, Option<&str>)> {
+    let cap = IMAGE_RE.captures(text)?;
+    let whole = cap.get(0)?;
+    Some((whole.start(), whole.end(), cap.get(1)?.as_str(), Some(cap.get(2)?.as_str())))
+}
 
-                new_nodes.push( TextNode {
-                    text: title.to_string(),
-                    text_type: TextType::Link,
-                    url: Some(url.to_string()),
-                });
+/// Locates the first `$$display$$` formula in `text`.
+fn find_display_math(text: &str) -> Option<(usize, usize, &str, Option<&str>)> {
+    let start = text.find("$$")?;
+    let len = text[start + 2..].find("$$")?;
+    let tex = &text[start + 2..start + 2 + len];
+    (!tex.trim().is_empty()).then_some((start, start + len + 4, tex, None))
+}
 
-                if !after.is_empty() {
-                    // recursively apply to the rest of the text...
-                    let remaining_node = TextNode {
-                        text: after.to_string(),
-                        text_type: TextType::Plain,
-                        url: None,
-                    };
-                    let extra_nodes = split_nodes_link(vec![remaining_node])?;
-                    new_nodes.extend(extra_nodes);
-                }
-            },
-            _ => {
-                return Err(NodeError::ParseError("Split returned unexpected number of sections".to_string()));
-            },
-        }
-    }
-    Ok(new_nodes)
+/// Locates the first `$inline$` formula in `text`. As in pandoc, the opening
+/// `$` must be followed by a non-space and the closing one preceded by a
+/// non-space and not followed by a digit, so "$5 and $10" stays text.
+fn find_inline_math(text: &str) -> Option<(usize, usize, &str, Option<&str>)> {
+    let mut dollars = text.match_indices('$').map(|(i, _)| i);
+    let open = dollars.by_ref().find(|&open| {
+        !text[..open].ends_with('\\')
+            && text[open + 1..].chars().next().is_some_and(|c| !c.is_whitespace() && c != '$')
+    })?;
+    // whether a `$` can close doesn't depend on the opener, so when the first
+    // opener has no closer, no later one does either
+    let close = dollars.find(|&close| {
+        !text[..close].ends_with(char::is_whitespace)
+            && !text[..close].ends_with('\\')
+            && !text[close + 1..].starts_with(|c: char| c.is_ascii_digit())
+    })?;
+    Some((open, close + 1, &text[open + 1..close], None))
 }
 
-pub fn split_nodes_image(old_nodes: Vec<TextNode>) -> Result<Vec<TextNode>, NodeError> {
+/// Locates the first `_italic_` span in `text`. An opening `_` can't follow a
+/// letter or digit and a closing one can't be followed by one, so snake_case
+/// words stay text.
+fn find_italic(text: &str) -> Option<(usize, usize, &str, Option<&str>)> {
+    let mut underscores = text.match_indices('_').map(|(i, _)| i);
+    let open = underscores.by_ref().find(|&open| {
+        !text[..open].ends_with(char::is_alphanumeric)
+            && text[open + 1..].chars().next().is_some_and(|c| !c.is_whitespace())
+    })?;
+    // as with math, a closer that fits the first opener fits any later one
+    let close = underscores.find(|&close| {
+        close > open + 1
+            && !text[..close].ends_with(char::is_whitespace)
+            && !text[close + 1..].starts_with(char::is_alphanumeric)
+    })?;
+    Some((open, close + 1, &text[open + 1..close], None))
+}
+
+/// Splits every plain node around the spans reported by `find`, turning each
+/// span into a node of `text_type`. Works iteratively so that a paragraph with
+/// thousands of links can't exhaust the stack.
+fn split_nodes_with<F>(old_nodes: Vec<TextNode>, text_type: TextType, find: F) -> Vec<TextNode>
+where
+    F: Fn(&str) -> Option<(usize, usize, &str, Option<&str>)>,
+{
     let mut new_nodes = Vec::new();
 
     for node in old_nodes {
-        if node.text.is_empty() {
+        // an image with no alt text is still an image
+        if node.text.is_empty() && node.text_type != TextType::Image {
             continue;
         }
         if node.text_type != TextType::Plain {
             new_nodes.push(node);
             continue;
         }
-        let images = extract_markdown_images(&node.text);
-        if images.is_empty() {
-            new_nodes.push(node);
-            continue;
-        }
-        let (alt_text, url) = images.get(0).unwrap();
-        let pat = format!("![{}]({})", alt_text, url);
-        //println!("Hunting for pattern: {}", pat);
-        let sections: Vec<_> = node.text.splitn(2, pat.as_str()).collect();
-        match sections.as_slice() {
-            [] => {
-                return Err(NodeError::ParseError("Split returned empty result".to_string()));
-            },
-            [text] => {
+
+        let mut rest = node.text.as_str();
+        while let Some((start, end, text, url)) = find(rest) {
+            if start > 0 {
                 new_nodes.push( TextNode {
-                    text: text.to_string(),
+                    text: rest[..start].to_string(),
                     text_type: TextType::Plain,
                     url: None,
                 });
-            },
-            [before, after] => {
-                if !before.is_empty() {
-                    new_nodes.push( TextNode {
-                        text: before.to_string(),
-                        text_type: TextType::Plain,
-                        url: None,
-                    });
-                }
+            }
 
-                new_nodes.push( TextNode {
-                    text: alt_text.to_string(),
-                    text_type: TextType::Image,
-                    url: Some(url.to_string()),
-                });
+            new_nodes.push( TextNode {
+                text: text.to_string(),
+                text_type,
+                url: url.map(str::to_string),
+            });
 
-                if !after.is_empty() {
-                    // recursively apply to the rest of the text...
-                    let remaining_node = TextNode {
-                        text: after.to_string(),
-                        text_type: TextType::Plain,
-                        url: None,
-                    };
-                    let extra_nodes = split_nodes_image(vec![remaining_node])?;
-                    new_nodes.extend(extra_nodes);
-                }
-            },
-            _ => {
-                return Err(NodeError::ParseError("Split returned unexpected number of sections".to_string()));
-            },
+            rest = &rest[end..];
+        }
+
+        if !rest.is_empty() {
+            new_nodes.push( TextNode {
+                text: rest.to_string(),
+                text_type: TextType::Plain,
+                url: None,
+            });
         }
     }
-    Ok(new_nodes)
+    new_nodes
+}
+
+pub fn split_nodes_math(old_nodes: Vec<TextNode>) -> Result<Vec<TextNode>, NodeError> {
+    let display = split_nodes_with(old_nodes, TextType::DisplayMath, find_display_math);
+    Ok(split_nodes_with(display, TextType::Math, find_inline_math))
+}
+
+pub fn split_nodes_italic(old_nodes: Vec<TextNode>) -> Result<Vec<TextNode>, NodeError> {
+    Ok(split_nodes_with(old_nodes, TextType::Italic, find_italic))
+}
+
+pub fn split_nodes_link(old_nodes: Vec<TextNode>) -> Result<Vec<TextNode>, NodeError> {
+    Ok(split_nodes_with(old_nodes, TextType::Link, find_markdown_link))
+}
+
+pub fn split_nodes_image(old_nodes: Vec<TextNode>) -> Result<Vec<TextNode>, NodeError> {
+    Ok(split_nodes_with(old_nodes, TextType::Image, find_markdown_image))
 }
 
 pub fn text_to_textnodes(text: &str) -> Result<Vec<TextNode>, NodeError> {
@@ -213,8 +239,11 @@ pub fn text_to_textnodes(text: &str) -> Result<Vec<TextNode>, NodeError> {
     };
     let mut final_nodes = vec![inital_node];
 
-    final_nodes = split_nodes_delimeter(final_nodes, "_", TextType::Italic)?;
+    // code spans first, so nothing inside them is read as markup or math, then
+    // math, so `$a_1$` isn't read as italics
     final_nodes = split_nodes_delimeter(final_nodes, "`", TextType::Code)?;
+    final_nodes = split_nodes_math(final_nodes)?;
+    final_nodes = split_nodes_italic(final_nodes)?;
     final_nodes = split_nodes_delimeter(final_nodes, "**", TextType::Bold)?;
     final_nodes = split_nodes_image(final_nodes)?;
     final_nodes = split_nodes_link(final_nodes)?;
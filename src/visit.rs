@@ -0,0 +1,119 @@
+use crate::htmlnode::HtmlNode;
+
+/// Read-only pass over an `HtmlNode` tree, driven by `HtmlNode::accept`.
+pub trait Visitor {
+    /// Called before a node's children. Returning `false` skips the children.
+    fn enter(&mut self, _node: &HtmlNode) -> bool {
+        true
+    }
+
+    /// Called after a node's children.
+    fn leave(&mut self, _node: &HtmlNode) {}
+}
+
+/// Mutating pass over an `HtmlNode` tree, driven by `HtmlNode::accept_mut`.
+pub trait VisitorMut {
+    /// Called before a node's children, which it may add to or remove.
+    /// Returning `false` skips the children.
+    fn enter(&mut self, _node: &mut HtmlNode) -> bool {
+        true
+    }
+
+    /// Called after a node's children.
+    fn leave(&mut self, _node: &mut HtmlNode) {}
+}
+
+impl HtmlNode {
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        if visitor.enter(self) {
+            for child in &self.children {
+                child.accept(visitor);
+            }
+        }
+        visitor.leave(self);
+    }
+
+    pub fn accept_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        if visitor.enter(self) {
+            for child in self.children.iter_mut() {
+                child.accept_mut(visitor);
+            }
+        }
+        visitor.leave(self);
+    }
+
+    /// Calls `f` on this node and every descendant, parents before children.
+    pub fn walk<F: FnMut(&HtmlNode)>(&self, mut f: F) {
+        self.walk_ref(&mut f);
+    }
+
+    fn walk_ref<F: FnMut(&HtmlNode)>(&self, f: &mut F) {
+        f(self);
+        for child in &self.children {
+            child.walk_ref(f);
+        }
+    }
+
+    /// Calls `f` on this node and every descendant, parents before children.
+    /// Children added by `f` are visited too.
+    pub fn walk_mut<F: FnMut(&mut HtmlNode)>(&mut self, mut f: F) {
+        self.walk_mut_ref(&mut f);
+    }
+
+    fn walk_mut_ref<F: FnMut(&mut HtmlNode)>(&mut self, f: &mut F) {
+        f(self);
+        for child in self.children.iter_mut() {
+            child.walk_mut_ref(f);
+        }
+    }
+
+    /// Removes every descendant for which `keep` returns `false`, along with
+    /// its subtree. This node itself is always kept.
+    pub fn retain<F: FnMut(&HtmlNode) -> bool>(&mut self, mut keep: F) {
+        self.retain_ref(&mut keep);
+    }
+
+    fn retain_ref<F: FnMut(&HtmlNode) -> bool>(&mut self, keep: &mut F) {
+        let had_children = !self.children.is_empty();
+        self.children.retain(|child| keep(child));
+        // a parent left with nothing serializes as an empty element
+        if had_children && self.children.is_empty() && self.value.is_none() {
+            self.value = Some(String::new());
+        }
+        for child in self.children.iter_mut() {
+            child.retain_ref(keep);
+        }
+    }
+
+    /// Swaps out every descendant for which `f` returns a replacement. The
+    /// replacement is not searched further; other nodes are searched recursively.
+    pub fn replace_with<F: FnMut(&HtmlNode) -> Option<HtmlNode>>(&mut self, mut f: F) {
+        self.replace_with_ref(&mut f);
+    }
+
+    fn replace_with_ref<F: FnMut(&HtmlNode) -> Option<HtmlNode>>(&mut self, f: &mut F) {
+        for child in self.children.iter_mut() {
+            match f(child) {
+                Some(replacement) => **child = replacement,
+                None => child.replace_with_ref(f),
+            }
+        }
+    }
+
+    /// Every node in the tree, this one included, that matches `predicate`,
+    /// in document order.
+    pub fn find_all<P: Fn(&HtmlNode) -> bool>(&self, predicate: P) -> Vec<&HtmlNode> {
+        let mut found = Vec::new();
+        self.find_all_ref(&predicate, &mut found);
+        found
+    }
+
+    fn find_all_ref<'a, P: Fn(&HtmlNode) -> bool>(&'a self, predicate: &P, found: &mut Vec<&'a HtmlNode>) {
+        if predicate(self) {
+            found.push(self);
+        }
+        for child in &self.children {
+            child.find_all_ref(predicate, found);
+        }
+    }
+}